@@ -0,0 +1,48 @@
+//! The `v16` demo, run against the library.
+
+use spans::json::{tokenize, Token};
+use spans::{
+    process_span, visualize_span, CliFormat, Coordinate, Format, JsonFormat, Pos, Raw, RenderInput,
+    Span,
+};
+
+/// The coordinate system a format's spans are produced in, without `type_name`
+fn span_type<F: Format>(_format: &F) -> &'static str {
+    <F::SpanType as Coordinate>::NAME
+}
+
+fn main() {
+    // Test with CliFormat (SpanType = Raw)
+    let cli_format = CliFormat;
+    let cli_args = ["--foo", "bar"];
+    let cli_input = &cli_args[..];
+    let raw_span = Span::<Raw<CliFormat>>::new(1, 1); // Second arg (index 1), length 1 in raw terms
+
+    println!("=== Test 1: CliFormat with Raw span ===");
+    println!("Input: {:?}", cli_input);
+    println!("Raw span: {:?}", raw_span);
+    println!("F::SpanType = {}", span_type(&cli_format));
+
+    let result1 = process_span(&cli_format, raw_span, cli_input);
+    println!("Cooked span: {:?}", result1);
+    visualize_span(cli_format.render_input(cli_input), &result1);
+
+    println!();
+
+    // Test with JsonFormat (SpanType = Cooked)
+    let json_format = JsonFormat;
+    let json_input = r#"{"foo": "bar"}"#.as_bytes();
+    // "foo" field position in JSON: the first string token, without its quotes
+    let mut tokens = tokenize::<Pos>(json_input).expect("document fits in a Pos");
+    let key = tokens.find(|token| token.node == Token::String);
+    let cooked_span = key.expect("document has a key").span.shrink(1, 1);
+
+    println!("=== Test 2: JsonFormat with Cooked span ===");
+    println!("Input: {}", String::from_utf8_lossy(json_input));
+    println!("Cooked span: {:?}", cooked_span);
+    println!("F::SpanType = {}", span_type(&json_format));
+
+    let result2 = process_span(&json_format, cooked_span, json_input);
+    println!("Result (unchanged): {:?}", result2);
+    visualize_span(json_input, &result2);
+}
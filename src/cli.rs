@@ -0,0 +1,516 @@
+//! CLI args: the string-like types raw CLI spans cook over, and [`ArgSpan`] for positions
+//! inside individual args, like `--key=value` and `-abc` clusters.
+
+use core::fmt;
+use core::marker::PhantomData;
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+
+use crate::format::{cooked_pos, CliFormat, CookError, CookRaw, Format, ToCooked};
+use crate::pos::{Pos, Position};
+use crate::render::RenderInput;
+use crate::span::{Cooked, Raw, Span};
+use crate::uncook::{raw_pos, FromCooked, RawError};
+use crate::validate::{check_bounds, wide, SpanError};
+
+/// A string-like CLI arg: `&str`, `String`, `Cow<str>`, `OsString`, ...
+///
+/// Raw CLI spans cook into byte offsets in the args joined with single spaces, with each
+/// arg as given by [`CliArg::arg_text`]. `OsStr` args that aren't valid UTF-8 are rendered
+/// lossily, each invalid sequence becoming U+FFFD (3 bytes), and cooked spans index into
+/// that rendering.
+pub trait CliArg {
+    /// The arg as it appears in the rendered command line
+    fn arg_text(&self) -> Cow<'_, str>;
+}
+
+impl CliArg for str {
+    fn arg_text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl CliArg for String {
+    fn arg_text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl CliArg for Box<str> {
+    fn arg_text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl CliArg for Cow<'_, str> {
+    fn arg_text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl CliArg for OsStr {
+    fn arg_text(&self) -> Cow<'_, str> {
+        self.to_string_lossy()
+    }
+}
+
+impl CliArg for OsString {
+    fn arg_text(&self) -> Cow<'_, str> {
+        self.to_string_lossy()
+    }
+}
+
+impl<T: CliArg + ?Sized> CliArg for &T {
+    fn arg_text(&self) -> Cow<'_, str> {
+        (**self).arg_text()
+    }
+}
+
+/// What the args are joined with in the command line. Rendering and cooking both use it, so
+/// the two can't drift apart.
+const SEPARATOR: &str = " ";
+
+/// The command line raw CLI spans cook into: the args joined with single spaces
+pub fn render_args<S: CliArg>(input: &[S]) -> String {
+    let args: Vec<_> = input.iter().map(CliArg::arg_text).collect();
+    args.join(SEPARATOR)
+}
+
+/// A raw format whose input is a list of CLI args, joined with single spaces when cooked.
+///
+/// Implementing this gives the format its [`CookRaw`] conversion: a raw span's `start` is
+/// an arg index and `len` a count of args. The CLI formats in [`format`](crate::format)
+/// implement it for `[&str]`, `[String]`, `[Cow<str>]` and `[OsString]`.
+pub trait ArgsFormat: Format<SpanType = Raw<Self>> + Sized {
+    /// The type of each arg in the input
+    type Arg<'input>: CliArg;
+
+    /// The args in `input`
+    fn args<'a, 'input: 'a>(input: &'a Self::Input<'input>) -> &'a [Self::Arg<'input>];
+}
+
+impl<F: ArgsFormat> CookRaw for F {
+    fn cook_raw<P: Position>(
+        &self,
+        span: Span<Raw<F>, P>,
+        input: &F::Input<'_>,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        ArgOffsets::new(F::args(input)).cook(span)
+    }
+
+    fn uncook_raw<P: Position>(
+        &self,
+        span: Span<Cooked, P>,
+        input: &F::Input<'_>,
+    ) -> Result<Span<Raw<F>, P>, RawError> {
+        ArgOffsets::new(F::args(input)).uncook(span)
+    }
+}
+
+/// Where each CLI arg starts in the space-joined command line.
+///
+/// This is what cooking and uncooking raw CLI spans are defined by: [`CookRaw`] for
+/// [`ArgsFormat`]s measures the args each time, and [`CookContext`](crate::CookContext) keeps
+/// the measurement for many spans.
+#[derive(Debug, Clone)]
+pub(crate) struct ArgOffsets {
+    /// Where each arg starts, then where another arg would start after the last one (as if
+    /// the line ended in a separator), so arg `i` ends at `starts[i + 1] - SEPARATOR.len()`
+    starts: Vec<usize>,
+}
+
+impl ArgOffsets {
+    /// Measures `args`, in O(M) for M args
+    pub(crate) fn new<S: CliArg>(args: &[S]) -> Self {
+        let mut starts = vec![0];
+        starts.extend(args.iter().scan(0, |next, arg| {
+            *next += arg.arg_text().len() + SEPARATOR.len();
+            Some(*next)
+        }));
+        Self { starts }
+    }
+
+    /// The number of args
+    pub(crate) fn arg_count(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    /// The length of the joined command line in bytes
+    pub(crate) fn line_len(&self) -> usize {
+        self.starts[self.arg_count()].saturating_sub(SEPARATOR.len())
+    }
+
+    /// Where arg `arg` ends
+    fn arg_end(&self, arg: usize) -> usize {
+        self.starts[arg + 1] - SEPARATOR.len()
+    }
+
+    /// Cook a raw span of arg indices, in O(1)
+    pub(crate) fn cook<F, P: Position>(
+        &self,
+        span: Span<Raw<F>, P>,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        let (first, end) = check_bounds(&span, self.arg_count())?;
+        let start = self.starts[first];
+
+        // A point sits between args: at the separator after the previous arg, or at the very
+        // start. One past the last arg is then the end of the line, with no separator after it.
+        if first == end {
+            let gap = start.saturating_sub(SEPARATOR.len());
+            return Ok(Span::point(cooked_pos(gap)?));
+        }
+
+        // The covered args, and the spaces separating them
+        Ok(Span::new(
+            cooked_pos(start)?,
+            cooked_pos(self.arg_end(end - 1) - start)?,
+        ))
+    }
+
+    /// Convert a cooked span back to arg indices, in O(log M) for M args
+    pub(crate) fn uncook<F, P: Position>(
+        &self,
+        span: Span<Cooked, P>,
+    ) -> Result<Span<Raw<F>, P>, RawError> {
+        let (start, end) = check_bounds(&span, self.line_len())?;
+
+        // A point is at the separator before an arg (or the very start, or the end of the line).
+        // An empty arg cooks to one of these too, and converts back as the gap: see
+        // [`uncook`](crate::uncook) for this exception to the round-trip laws.
+        if start == end {
+            if start == 0 {
+                return Ok(Span::point(P::ZERO));
+            }
+            return match self.starts.binary_search(&(start + SEPARATOR.len())) {
+                Ok(after) => Ok(Span::point(raw_pos(after)?)),
+                Err(_) => Err(self.misplaced_boundary(start)),
+            };
+        }
+
+        // Otherwise the span must start at the start of an arg, and end at the end of one
+        let first = self.starts[..self.arg_count()].binary_search(&start);
+        let first = first.map_err(|_| self.misplaced_boundary(start))?;
+        let after = self.starts.binary_search(&(end + SEPARATOR.len()));
+        let after = after.map_err(|_| match self.misplaced_boundary(end) {
+            // The span's last byte is the separator, not the boundary after it
+            RawError::OnSeparator { pos } => RawError::OnSeparator { pos: pos - 1 },
+            e => e,
+        })?;
+        Ok(Span::new(raw_pos(first)?, raw_pos(after - first)?))
+    }
+
+    /// The arg the cooked range `start..end` lies within, and where that arg starts
+    pub(crate) fn arg_within(&self, start: usize, end: usize) -> Result<(usize, usize), RawError> {
+        // The last arg starting at or before `start`
+        let arg = self.starts.partition_point(|&arg_start| arg_start <= start) - 1;
+        if arg < self.arg_count() && end <= self.arg_end(arg) {
+            return Ok((arg, self.starts[arg]));
+        }
+        // Either the span starts on a separator, or it runs on past the end of its arg
+        let pos = match arg < self.arg_count() && start <= self.arg_end(arg) {
+            true => self.arg_end(arg),
+            false => start,
+        };
+        Err(RawError::OnSeparator { pos: pos as u64 })
+    }
+
+    /// The error for a span boundary at `pos` that isn't at the start or end of an arg
+    fn misplaced_boundary(&self, pos: usize) -> RawError {
+        // The last arg starting at or before `pos`
+        let arg = self.starts.partition_point(|&start| start <= pos) - 1;
+        if arg < self.arg_count() && self.starts[arg] < pos && pos < self.arg_end(arg) {
+            RawError::Straddles {
+                arg: arg as u64,
+                pos: pos as u64,
+            }
+        } else {
+            RawError::OnSeparator { pos: pos as u64 }
+        }
+    }
+}
+
+impl<F: ArgsFormat> RenderInput for F {
+    fn render_input<'a>(&self, input: &'a F::Input<'_>) -> Cow<'a, [u8]> {
+        Cow::Owned(render_args(F::args(input)).into_bytes())
+    }
+}
+
+/// A raw CLI position finer than a whole arg: a byte range inside the arg at index `arg`.
+///
+/// Whole-arg (and multi-arg) spans are still `Span<Raw<F>>`; this is for pointing at the
+/// value in `--level=9000` or the `c` in `-abc`. Like `Raw<F>`, it is tagged with the CLI
+/// format `F` it was produced by, and cooks via [`ToCooked`] against that format into the
+/// exact bytes in the joined command line. A span that starts or ends inside a char of the
+/// arg converts neither way, failing with [`SpanError::NotCharBoundary`].
+pub struct ArgSpan<F = CliFormat, P = Pos> {
+    /// Index of the arg
+    pub arg: P,
+    /// Byte offset of the span within the arg
+    pub offset: P,
+    /// Length of the span in bytes
+    pub len: P,
+    _f: PhantomData<fn() -> F>,
+}
+
+impl<F, P: Position> ArgSpan<F, P> {
+    /// Creates a span of `len` bytes starting `offset` bytes into arg `arg`
+    pub fn new(arg: P, offset: P, len: P) -> Self {
+        Self {
+            arg,
+            offset,
+            len,
+            _f: PhantomData,
+        }
+    }
+
+    /// The `value` in a `--key=value` arg, or `None` if the arg has no `=`
+    pub fn value_of<S: CliArg>(input: &[S], arg: P) -> Option<Self> {
+        let text = input.get(arg.to_usize()?)?.arg_text();
+        let eq = text.find('=')?;
+        Self::within(arg, eq + 1, text.len() - eq - 1)
+    }
+
+    /// The `--key` in a `--key=value` arg, or `None` if the arg has no `=`
+    pub fn key_of<S: CliArg>(input: &[S], arg: P) -> Option<Self> {
+        let text = input.get(arg.to_usize()?)?.arg_text();
+        Self::within(arg, 0, text.find('=')?)
+    }
+
+    /// The `index`th flag in a `-abc` cluster of short flags, or `None` if the arg isn't a
+    /// cluster or is too short
+    pub fn cluster_flag<S: CliArg>(input: &[S], arg: P, index: usize) -> Option<Self> {
+        let text = input.get(arg.to_usize()?)?.arg_text();
+        let flags = text.strip_prefix('-').filter(|f| !f.starts_with('-'))?;
+        let (offset, flag) = flags.char_indices().nth(index)?;
+        Self::within(arg, 1 + offset, flag.len_utf8())
+    }
+
+    fn within(arg: P, offset: usize, len: usize) -> Option<Self> {
+        Some(Self::new(arg, P::from_usize(offset)?, P::from_usize(len)?))
+    }
+
+    /// The whole-arg raw span containing this one
+    pub fn whole_arg(&self) -> Span<Raw<F>, P> {
+        Span::new(self.arg, P::ONE)
+    }
+}
+
+impl<'input, F: ArgsFormat, P: Position> ToCooked<'input, F, P> for ArgSpan<F, P> {
+    type Error = CookError;
+
+    fn try_to_cooked(
+        self,
+        format: &F,
+        input: &F::Input<'input>,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        // Cook the whole arg, then narrow it to the bytes inside it
+        let arg = format.cook_raw(self.whole_arg(), input)?;
+        let end = self
+            .offset
+            .checked_add(self.len)
+            .filter(|&end| end <= arg.len);
+        let end = end.ok_or(CookError::OutsideArg {
+            arg: wide(self.arg),
+            end: wide(self.offset.saturating_add(self.len)),
+            arg_len: wide(arg.len),
+        })?;
+        // The arg exists, as it cooked
+        let text = self.arg.to_usize().and_then(|i| F::args(input).get(i));
+        let text = text.map(CliArg::arg_text).unwrap_or_default();
+        let splits_char = |pos: P| pos.to_usize().is_none_or(|pos| !text.is_char_boundary(pos));
+        if let Some(pos) = [self.offset, end].into_iter().find(|&pos| splits_char(pos)) {
+            return Err(SpanError::NotCharBoundary { pos: wide(pos) }.into());
+        }
+        let start = arg
+            .start
+            .checked_add(self.offset)
+            .ok_or(CookError::Overflow)?;
+        Ok(Span::new(start, self.len))
+    }
+}
+
+impl<'input, F: ArgsFormat, P: Position> FromCooked<'input, F, P> for ArgSpan<F, P> {
+    /// The arg a cooked span falls in, and where in it: e.g. for a cursor position or a
+    /// click in the rendered command line
+    fn try_from_cooked(
+        span: Span<Cooked, P>,
+        _format: &F,
+        input: &F::Input<'input>,
+    ) -> Result<Self, RawError> {
+        let offsets = ArgOffsets::new(F::args(input));
+        let (start, end) = check_bounds(&span, offsets.line_len())?;
+        let (arg, arg_start) = offsets.arg_within(start, end)?;
+        let text = F::args(input)[arg].arg_text();
+        if let Some(pos) = [start, end]
+            .into_iter()
+            .find(|&pos| !text.is_char_boundary(pos - arg_start))
+        {
+            return Err(SpanError::NotCharBoundary { pos: pos as u64 }.into());
+        }
+        let raw = |n: usize| P::from_usize(n).ok_or(RawError::Overflow);
+        Ok(Self::new(
+            raw(arg)?,
+            raw(start - arg_start)?,
+            raw(end - start)?,
+        ))
+    }
+}
+
+// Written by hand so neither `F` nor `P` needs more than `Span` asks of them.
+
+impl<F, P: Position> Clone for ArgSpan<F, P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F, P: Position> Copy for ArgSpan<F, P> {}
+
+impl<F, P: Position> PartialEq for ArgSpan<F, P> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.arg, self.offset, self.len) == (other.arg, other.offset, other.len)
+    }
+}
+
+impl<F, P: Position> Eq for ArgSpan<F, P> {}
+
+impl<F, P: Position> core::hash::Hash for ArgSpan<F, P> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        (self.arg, self.offset, self.len).hash(state);
+    }
+}
+
+/// Shown as `arg:offset+len`
+impl<F, P: Position> fmt::Display for ArgSpan<F, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}+{}", self.arg, self.offset, self.len)
+    }
+}
+
+impl<F, P: Position> fmt::Debug for ArgSpan<F, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Arg[{self}]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{CowCliFormat, OsCliFormat, OwnedCliFormat};
+
+    const ARGS: &[&str] = &["run", "--level=9000", "-abc"];
+
+    fn cook(span: ArgSpan) -> Result<Span<Cooked>, CookError> {
+        span.try_to_cooked(&CliFormat, ARGS)
+    }
+
+    /// The text `span` cooks to in the joined command line
+    fn text(span: ArgSpan) -> String {
+        render_args(ARGS).as_str()[cook(span).unwrap()].to_owned()
+    }
+
+    #[test]
+    fn finds_keys_and_values() {
+        let value = ArgSpan::value_of(ARGS, 1).unwrap();
+        assert_eq!(value, ArgSpan::new(1, 8, 4));
+        assert_eq!(text(value), "9000");
+        let key = ArgSpan::key_of(ARGS, 1).unwrap();
+        assert_eq!(text(key), "--level");
+        assert_eq!(ArgSpan::<CliFormat>::value_of(ARGS, 0), None);
+        assert_eq!(ArgSpan::<CliFormat>::key_of(ARGS, 3), None);
+    }
+
+    #[test]
+    fn finds_flags_in_clusters() {
+        let flag = ArgSpan::cluster_flag(ARGS, 2, 2).unwrap();
+        assert_eq!(flag, ArgSpan::new(2, 3, 1));
+        assert_eq!(text(flag), "c");
+        assert_eq!(ArgSpan::<CliFormat>::cluster_flag(ARGS, 2, 3), None);
+        assert_eq!(ArgSpan::<CliFormat>::cluster_flag(ARGS, 1, 0), None);
+    }
+
+    #[test]
+    fn reports_spans_outside_the_arg() {
+        assert_eq!(
+            cook(ArgSpan::new(0, 2, 2)),
+            Err(CookError::OutsideArg {
+                arg: 0,
+                end: 4,
+                arg_len: 3
+            })
+        );
+        assert!(matches!(
+            cook(ArgSpan::new(3, 0, 0)),
+            Err(CookError::Span(SpanError::EndOutOfRange { .. }))
+        ));
+    }
+
+    #[test]
+    fn converts_back_from_cooked() {
+        let from = |start, len| {
+            ArgSpan::<CliFormat>::try_from_cooked(Span::new(start, len), &CliFormat, ARGS)
+        };
+        assert_eq!(from(12, 4), Ok(ArgSpan::new(1, 8, 4)));
+        assert_eq!(from(19, 1), Ok(ArgSpan::new(2, 2, 1)));
+        assert!(matches!(from(2, 4), Err(RawError::OnSeparator { pos: 3 })));
+    }
+
+    #[test]
+    fn rejects_spans_inside_a_char() {
+        let args = ["caf\u{e9}=x"];
+        let cook = |offset, len| {
+            ArgSpan::<CliFormat>::new(0, offset, len).try_to_cooked(&CliFormat, &args[..])
+        };
+        assert_eq!(
+            cook(4, 1),
+            Err(CookError::Span(SpanError::NotCharBoundary { pos: 4 }))
+        );
+        assert_eq!(
+            cook(0, 4),
+            Err(CookError::Span(SpanError::NotCharBoundary { pos: 4 }))
+        );
+        assert_eq!(cook(3, 2), Ok(Span::new(3, 2)));
+        let from = ArgSpan::<CliFormat>::try_from_cooked(Span::new(0, 4), &CliFormat, &args[..]);
+        assert_eq!(
+            from,
+            Err(RawError::Span(SpanError::NotCharBoundary { pos: 4 }))
+        );
+        assert_eq!(
+            ArgSpan::<CliFormat>::new(0, 3, 2).whole_arg(),
+            Span::new(0, 1)
+        );
+    }
+
+    /// Where the second arg is, in any CLI format
+    fn second_arg<F: ArgsFormat + Default + 'static>(input: &F::Input<'_>) -> Span<Cooked> {
+        Span::<Raw<F>>::new(1, 1).to_cooked(&F::default(), input)
+    }
+
+    #[test]
+    fn cooks_any_string_like_args() {
+        let owned: Vec<String> = ARGS.iter().map(|&arg| arg.into()).collect();
+        let cows: Vec<Cow<str>> = vec![Cow::Borrowed("run"), Cow::Owned("--level=9000".into())];
+        let os: Vec<OsString> = ARGS.iter().map(OsString::from).collect();
+        let expected: Span<Cooked> = Span::new(4, 12);
+        assert_eq!(second_arg::<CliFormat>(ARGS), expected);
+        assert_eq!(second_arg::<OwnedCliFormat>(&owned), expected);
+        assert_eq!(second_arg::<CowCliFormat>(&cows), expected);
+        assert_eq!(second_arg::<OsCliFormat>(&os), expected);
+        assert_eq!(
+            ArgSpan::value_of(&owned, 1),
+            ArgSpan::<OwnedCliFormat>::value_of(ARGS, 1)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cooks_os_args_as_rendered_lossily() {
+        use std::os::unix::ffi::OsStringExt;
+        // `\xff` isn't UTF-8, so renders as the 3 bytes of U+FFFD
+        let args = [OsString::from_vec(b"a\xffb".to_vec()), OsString::from("c")];
+        assert_eq!(render_args(&args), "a\u{fffd}b c");
+        let c = Span::<Raw<OsCliFormat>>::new(1, 1).to_cooked(&OsCliFormat, &args);
+        assert_eq!(c, Span::new(6, 1));
+        assert_eq!(&OsCliFormat.render_input(&args)[c], b"c");
+    }
+}
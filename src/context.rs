@@ -0,0 +1,169 @@
+//! [`CookContext`]: cooking many raw CLI spans against the same input.
+//!
+//! [`ToCooked`](crate::ToCooked) on a raw CLI span measures every arg in the input, so
+//! cooking N spans over M args is O(N·M). A context measures the args once, and then cooks
+//! each span in O(1) and converts each cooked span back in O(log M).
+
+use core::borrow::Borrow;
+
+use crate::cli::{ArgOffsets, ArgsFormat};
+use crate::format::{debug_validate, CookError};
+use crate::pos::Position;
+use crate::span::{Cooked, Raw, Span};
+use crate::uncook::RawError;
+
+/// A CLI format's input, with the byte offset of every arg in the joined command line.
+///
+/// Built once per input with [`CookContext::new`]. Its conversions are the same ones
+/// [`ToCooked`](crate::ToCooked) and [`ToRaw`](crate::ToRaw) make against the same format and
+/// input, without measuring the args again for each span.
+#[derive(Debug)]
+pub struct CookContext<'a, 'input, F: ArgsFormat> {
+    format: &'a F,
+    input: &'a F::Input<'input>,
+    offsets: ArgOffsets,
+}
+
+impl<'a, 'input, F: ArgsFormat> CookContext<'a, 'input, F> {
+    /// Measures the args in `input`, in O(M) for M args
+    pub fn new(format: &'a F, input: &'a F::Input<'input>) -> Self {
+        Self {
+            format,
+            input,
+            offsets: ArgOffsets::new(F::args(input)),
+        }
+    }
+
+    /// The format spans are cooked for
+    pub fn format(&self) -> &'a F {
+        self.format
+    }
+
+    /// The input spans are cooked against
+    pub fn input(&self) -> &'a F::Input<'input> {
+        self.input
+    }
+
+    /// The number of args in the input
+    pub fn arg_count(&self) -> usize {
+        self.offsets.arg_count()
+    }
+
+    /// The length of the joined command line in bytes
+    pub fn line_len(&self) -> usize {
+        self.offsets.line_len()
+    }
+
+    /// [`ToCooked::try_to_cooked`](crate::ToCooked::try_to_cooked), in O(1)
+    pub fn try_cook<P: Position>(
+        &self,
+        span: Span<Raw<F>, P>,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        self.offsets.cook(span)
+    }
+
+    /// [`ToCooked::to_cooked`](crate::ToCooked::to_cooked), in O(1)
+    ///
+    /// # Panics
+    ///
+    /// If [`CookContext::try_cook`] fails.
+    pub fn cook<P: Position>(&self, span: Span<Raw<F>, P>) -> Span<Cooked, P> {
+        self.try_cook(span)
+            .unwrap_or_else(|e| panic!("failed to cook span: {e}"))
+    }
+
+    /// Cooks every span in `spans` (a slice or an iterator), reporting each one's result
+    pub fn cook_all<P, I>(
+        &self,
+        spans: I,
+    ) -> impl Iterator<Item = Result<Span<Cooked, P>, CookError>> + use<'_, 'a, 'input, F, P, I>
+    where
+        P: Position,
+        I: IntoIterator,
+        I::Item: Borrow<Span<Raw<F>, P>>,
+    {
+        spans
+            .into_iter()
+            .map(move |span| self.try_cook(*span.borrow()))
+    }
+
+    /// [`process_span`](crate::process_span) against this context's input: with debug
+    /// assertions enabled, the span is checked with
+    /// [`Format::validate_span`](crate::Format::validate_span) first.
+    ///
+    /// # Panics
+    ///
+    /// If the span can't be cooked against the input.
+    pub fn process_span<P: Position>(&self, span: Span<Raw<F>, P>) -> Span<Cooked, P> {
+        debug_validate(self.format, &span, self.input);
+        self.cook(span)
+    }
+
+    /// [`ToRaw::try_to_raw`](crate::ToRaw::try_to_raw), in O(log M) for M args
+    pub fn uncook<P: Position>(&self, span: Span<Cooked, P>) -> Result<Span<Raw<F>, P>, RawError> {
+        self.offsets.uncook(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{CliFormat, ToCooked};
+    use crate::uncook::ToRaw;
+    use crate::validate::SpanError;
+
+    #[test]
+    fn agrees_with_to_cooked_and_to_raw() {
+        let inputs: &[&[&str]] = &[&[], &["--foo", "bar"], &["a", "", "b", "caf\u{e9}"], &[""]];
+        for &args in inputs {
+            let ctx = CookContext::new(&CliFormat, args);
+            let count = args.len() as u32;
+            for start in 0..=count + 1 {
+                for len in 0..=count + 1 - start {
+                    let span = Span::<Raw<CliFormat>>::new(start, len);
+                    assert_eq!(ctx.try_cook(span), span.try_to_cooked(&CliFormat, args));
+                }
+            }
+            let line_len = ctx.line_len() as u32;
+            for start in 0..=line_len + 1 {
+                for len in 0..=line_len + 1 - start {
+                    let span = Span::<Cooked>::new(start, len);
+                    assert_eq!(ctx.uncook(span), span.try_to_raw(&CliFormat, args));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cook_all() {
+        let args = ["--level=9000", "-v", "file"];
+        let ctx = CookContext::new(&CliFormat, &args[..]);
+        let spans = [
+            Span::new(0, 1),
+            Span::new(1, 2),
+            Span::new(3, 0),
+            Span::new(4, 0),
+        ];
+        let cooked: Vec<_> = ctx.cook_all(&spans).collect();
+        assert_eq!(
+            cooked,
+            [
+                Ok(Span::<Cooked>::new(0, 12)),
+                Ok(Span::new(13, 7)),
+                Ok(Span::point(20)),
+                Err(CookError::Span(SpanError::StartOutOfRange {
+                    start: 4,
+                    input_len: 3
+                })),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic = "process_span given an invalid span")]
+    #[cfg_attr(not(debug_assertions), should_panic = "failed to cook span")]
+    fn process_span_validates_like_the_free_function() {
+        let args = ["--foo"];
+        CookContext::new(&CliFormat, &args[..]).process_span(Span::<Raw<CliFormat>>::new(1, 1));
+    }
+}
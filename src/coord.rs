@@ -0,0 +1,82 @@
+//! The [`Coordinate`] trait implemented by span marker types.
+
+use crate::span::{Cooked, Raw};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A coordinate system a [`Span`](crate::Span) can be expressed in.
+///
+/// The trait is sealed: [`Cooked`] is the only byte offset coordinate system, and the only
+/// one whose spans cook to themselves. Other coordinate systems can be added by
+/// implementing [`RawCoordinate`], which implements this trait for them.
+pub trait Coordinate: sealed::Sealed + 'static {
+    /// A stable human-readable name, used in `Debug` output and diagnostics
+    const NAME: &'static str;
+    /// Whether positions are byte offsets into the input
+    const IS_BYTE_OFFSET: bool;
+    /// Whether cooking a span in this coordinate system returns it unchanged, so generic
+    /// code can skip the conversion entirely (as [`try_process_span`](crate::try_process_span)
+    /// does)
+    const COOK_IS_IDENTITY: bool;
+}
+
+/// A format-specific coordinate system whose spans need cooking into byte offsets.
+///
+/// Implementing this is how coordinate systems other than [`Raw<F>`] and [`Cooked`] are added.
+pub trait RawCoordinate: 'static {
+    /// A stable human-readable name, used in `Debug` output and diagnostics
+    const NAME: &'static str;
+}
+
+impl<T: RawCoordinate> sealed::Sealed for T {}
+
+impl<T: RawCoordinate> Coordinate for T {
+    const NAME: &'static str = T::NAME;
+    const IS_BYTE_OFFSET: bool = false;
+    const COOK_IS_IDENTITY: bool = false;
+}
+
+impl<F: 'static> RawCoordinate for Raw<F> {
+    const NAME: &'static str = "Raw";
+}
+
+impl sealed::Sealed for Cooked {}
+
+impl Coordinate for Cooked {
+    const NAME: &'static str = "Cooked";
+    const IS_BYTE_OFFSET: bool = true;
+    const COOK_IS_IDENTITY: bool = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{CliFormat, CookedFormat, Format, JsonFormat, RawFormat};
+
+    enum Lines {}
+
+    impl RawCoordinate for Lines {
+        const NAME: &'static str = "Lines";
+    }
+
+    /// The constants of `C`, as generic code sees them
+    fn constants<C: Coordinate>() -> (&'static str, bool, bool) {
+        (C::NAME, C::IS_BYTE_OFFSET, C::COOK_IS_IDENTITY)
+    }
+
+    /// The constants of `F`'s coordinate system
+    fn format_constants<F: Format>() -> (&'static str, bool, bool) {
+        constants::<F::SpanType>()
+    }
+
+    #[test]
+    fn only_cooked_is_the_identity() {
+        assert_eq!(format_constants::<JsonFormat>(), ("Cooked", true, true));
+        assert_eq!(format_constants::<CookedFormat>(), ("Cooked", true, true));
+        assert_eq!(format_constants::<CliFormat>(), ("Raw", false, false));
+        assert_eq!(format_constants::<RawFormat>(), ("Raw", false, false));
+        assert_eq!(constants::<Lines>(), ("Lines", false, false));
+    }
+}
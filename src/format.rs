@@ -0,0 +1,623 @@
+//! Formats, and converting their spans into cooked byte offsets.
+
+use core::fmt;
+use std::borrow::Cow;
+use std::ffi::OsString;
+
+use crate::cli::ArgsFormat;
+use crate::coord::Coordinate;
+use crate::pos::{Pos, Position};
+use crate::render::RenderInput;
+use crate::span::{Cooked, Raw, Span};
+use crate::uncook::{raw_pos, RawError};
+use crate::validate::{check_bounds, check_str, SpanError, Validate};
+
+/// Format trait with associated input and span types.
+///
+/// `SpanType` is the coordinate system the format's spans are produced in, and
+/// `Input` is what those spans index into (e.g. `[&str]` for CLI args, `[u8]` for JSON).
+pub trait Format {
+    /// The coordinate system of spans produced by this format ([`Raw<Self>`] or [`Cooked`]),
+    /// which must know how to cook its spans against this format's input
+    type SpanType: Coordinate + CookSpan<Self>;
+    /// The input that spans of this format describe
+    type Input<'input>: ?Sized;
+
+    /// Check that `span` lies within `input`, as [`process_span`] does under debug assertions.
+    ///
+    /// Formats that don't override this accept every span.
+    fn validate_span<P: Position>(
+        &self,
+        span: &Span<Self::SpanType, P>,
+        input: &Self::Input<'_>,
+    ) -> Result<(), SpanError> {
+        let _ = (span, input);
+        Ok(())
+    }
+}
+
+/// Why a span could not be cooked against an input.
+///
+/// Positions are in the raw span's own units: arg indices for [`CliFormat`], and chars for
+/// [`RawFormat`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CookError {
+    /// The span doesn't fit the input: e.g. it starts at an arg index past the last arg, or
+    /// crosses the end of the input
+    Span(SpanError),
+    /// A span inside a single CLI arg extends past the end of that arg
+    OutsideArg {
+        /// Index of the arg
+        arg: u64,
+        /// End of the span, as a byte offset within the arg
+        end: u64,
+        /// Length of the arg in bytes
+        arg_len: u64,
+    },
+    /// The cooked position doesn't fit in the span's position width
+    Overflow,
+}
+
+impl fmt::Display for CookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CookError::Span(e) => e.fmt(f),
+            CookError::OutsideArg { arg, end, arg_len } => {
+                write!(
+                    f,
+                    "span ends at byte {end} of arg {arg}, which is {arg_len} bytes long"
+                )
+            }
+            CookError::Overflow => f.write_str("cooked position overflows the span's width"),
+        }
+    }
+}
+
+impl std::error::Error for CookError {}
+
+impl From<SpanError> for CookError {
+    fn from(e: SpanError) -> Self {
+        CookError::Span(e)
+    }
+}
+
+/// Convert a cooked byte offset into the span's position width
+pub(crate) fn cooked_pos<P: Position>(n: usize) -> Result<P, CookError> {
+    P::from_usize(n).ok_or(CookError::Overflow)
+}
+
+/// The key trait for conversion, parameterised by Format and input.
+///
+/// Parameterising by `F` is what lets one impl cover every format with a given
+/// `SpanType`, instead of requiring each format to define its own conversion.
+/// `P` is the position width, which cooking preserves.
+///
+/// `'input` is only the lifetime inside the input type (the `'b` of `[&'b str]`), and the
+/// input is borrowed for any shorter lifetime, so a brief borrow of a `[&'static str]` can
+/// be cooked without tying the two together.
+pub trait ToCooked<'input, F: Format, P: Position = Pos>: Sized {
+    /// Why the span can't be cooked: [`CookError`], or for spans that can fail in ways of
+    /// their own, an error that wraps it
+    type Error: From<CookError> + fmt::Display;
+
+    /// Convert this span into the cooked (byte offset) coordinate system, or report why the
+    /// span doesn't fit the input
+    fn try_to_cooked(
+        self,
+        format: &F,
+        input: &F::Input<'input>,
+    ) -> Result<Span<Cooked, P>, Self::Error>;
+
+    /// Convert this span into the cooked (byte offset) coordinate system
+    ///
+    /// # Panics
+    ///
+    /// If [`ToCooked::try_to_cooked`] fails.
+    #[inline]
+    fn to_cooked(self, format: &F, input: &F::Input<'input>) -> Span<Cooked, P> {
+        self.try_to_cooked(format, input)
+            .unwrap_or_else(|e| panic!("failed to cook span: {e}"))
+    }
+}
+
+/// How spans in a coordinate system convert to and from cooked byte offsets for the format
+/// `F`.
+///
+/// This is part of the [`Format`] contract, as a bound on `SpanType`: so for any
+/// `F: Format`, a `Span<F::SpanType>` implements [`ToCooked`] (and a cooked span
+/// [`ToRaw`](crate::ToRaw)) without the caller having to say so. [`Cooked`] implements it as
+/// the identity for every format, and [`Raw<F>`] by delegating to `F`'s [`CookRaw`] hook.
+pub trait CookSpan<F: Format + ?Sized>: Sized {
+    /// Convert `span` into byte offsets in `input`, or report why it doesn't fit
+    fn cook_span<P: Position>(
+        format: &F,
+        span: Span<Self, P>,
+        input: &F::Input<'_>,
+    ) -> Result<Span<Cooked, P>, CookError>;
+
+    /// Convert a cooked span into this coordinate system, or report why it has no equivalent
+    fn uncook_span<P: Position>(
+        format: &F,
+        span: Span<Cooked, P>,
+        input: &F::Input<'_>,
+    ) -> Result<Span<Self, P>, RawError>;
+}
+
+impl<F: Format + ?Sized> CookSpan<F> for Cooked {
+    #[inline]
+    fn cook_span<P: Position>(
+        _format: &F,
+        span: Span<Cooked, P>,
+        _input: &F::Input<'_>,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        Ok(span)
+    }
+
+    #[inline]
+    fn uncook_span<P: Position>(
+        _format: &F,
+        span: Span<Cooked, P>,
+        _input: &F::Input<'_>,
+    ) -> Result<Span<Cooked, P>, RawError> {
+        Ok(span)
+    }
+}
+
+impl<F: CookRaw + 'static> CookSpan<F> for Raw<F> {
+    #[inline]
+    fn cook_span<P: Position>(
+        format: &F,
+        span: Span<Raw<F>, P>,
+        input: &F::Input<'_>,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        format.cook_raw(span, input)
+    }
+
+    #[inline]
+    fn uncook_span<P: Position>(
+        format: &F,
+        span: Span<Cooked, P>,
+        input: &F::Input<'_>,
+    ) -> Result<Span<Raw<F>, P>, RawError> {
+        format.uncook_raw(span, input)
+    }
+}
+
+/// Any span whose coordinate system [`CookSpan`] covers for `F`: `Span<F::SpanType>`, and
+/// `Span<Cooked>`, which passes through unchanged
+impl<'input, F: Format, C: CookSpan<F>, P: Position> ToCooked<'input, F, P> for Span<C, P> {
+    type Error = CookError;
+
+    #[inline]
+    fn try_to_cooked(
+        self,
+        format: &F,
+        input: &F::Input<'input>,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        C::cook_span(format, self, input)
+    }
+}
+
+/// A raw format's own conversion between its raw coordinates and cooked byte offsets.
+///
+/// This is opt-in: only formats whose `SpanType` is [`Raw<Self>`] implement it, and
+/// cooking a `Span<Raw<F>>` (and converting back to one) delegates to it through
+/// [`CookSpan`]. Cooked formats such as [`JsonFormat`] have nothing to convert and write
+/// nothing.
+///
+/// Formats whose input is a list of CLI args get this from [`ArgsFormat`].
+pub trait CookRaw: Format<SpanType = Raw<Self>> + Sized {
+    /// Convert `span` into byte offsets in `input`, or report why it doesn't fit
+    fn cook_raw<P: Position>(
+        &self,
+        span: Span<Raw<Self>, P>,
+        input: &Self::Input<'_>,
+    ) -> Result<Span<Cooked, P>, CookError>;
+
+    /// Convert a cooked span back into this format's raw coordinates.
+    ///
+    /// Formats that don't override this report [`RawError::Unsupported`].
+    fn uncook_raw<P: Position>(
+        &self,
+        span: Span<Cooked, P>,
+        input: &Self::Input<'_>,
+    ) -> Result<Span<Raw<Self>, P>, RawError> {
+        let _ = (span, input);
+        Err(RawError::Unsupported)
+    }
+}
+
+macro_rules! cli_formats {
+    ($($(#[$doc:meta])* $name:ident => $arg:ty;)*) => {$(
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct $name;
+
+        impl Format for $name {
+            type SpanType = Raw<$name>;
+            type Input<'input> = [$arg];
+
+            fn validate_span<P: Position>(
+                &self,
+                span: &Span<Raw<$name>, P>,
+                input: &Self::Input<'_>,
+            ) -> Result<(), SpanError> {
+                span.validate(input)
+            }
+        }
+
+        impl ArgsFormat for $name {
+            type Arg<'input> = $arg;
+
+            fn args<'a, 'input: 'a>(input: &'a Self::Input<'input>) -> &'a [$arg] {
+                input
+            }
+        }
+    )*};
+}
+
+cli_formats! {
+    /// CLI Format: raw spans index into the argument list.
+    CliFormat => &'input str;
+    /// [`CliFormat`] over owned args, e.g. a `Vec<String>` from `std::env::args()`.
+    OwnedCliFormat => String;
+    /// [`CliFormat`] over args that may or may not be borrowed.
+    CowCliFormat => Cow<'input, str>;
+    /// [`CliFormat`] over OS args, e.g. from `std::env::args_os()`. Args that aren't valid
+    /// UTF-8 are rendered lossily (see [`CliArg`](crate::CliArg)).
+    OsCliFormat => OsString;
+}
+
+/// JSON Format: spans are byte offsets into the document, so already cooked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormat;
+
+impl Format for JsonFormat {
+    type SpanType = Cooked;
+    type Input<'input> = [u8];
+
+    fn validate_span<P: Position>(
+        &self,
+        span: &Span<Cooked, P>,
+        input: &[u8],
+    ) -> Result<(), SpanError> {
+        span.validate(input)
+    }
+}
+
+impl RenderInput for JsonFormat {
+    fn render_input<'a>(&self, input: &'a [u8]) -> Cow<'a, [u8]> {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Text whose raw spans count chars rather than bytes.
+///
+/// This is v1's `RawFormat`, whose "conversion" only relabelled the span: here cooking walks
+/// the input to turn char offsets into byte offsets.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawFormat;
+
+impl Format for RawFormat {
+    type SpanType = Raw<RawFormat>;
+    type Input<'input> = str;
+
+    fn validate_span<P: Position>(
+        &self,
+        span: &Span<Raw<RawFormat>, P>,
+        input: &str,
+    ) -> Result<(), SpanError> {
+        span.validate(input)
+    }
+}
+
+impl CookRaw for RawFormat {
+    fn cook_raw<P: Position>(
+        &self,
+        span: Span<Raw<RawFormat>, P>,
+        input: &str,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        let (start_char, end_char) = check_bounds(&span, input.chars().count())?;
+        // Byte offset of every char boundary, including the end of the input
+        let mut boundaries = input.char_indices().map(|(i, _)| i).chain([input.len()]);
+        let start = boundaries.nth(start_char).unwrap_or(input.len());
+        let end = match end_char - start_char {
+            0 => start,
+            n => boundaries.nth(n - 1).unwrap_or(input.len()),
+        };
+        Ok(Span::new(cooked_pos(start)?, cooked_pos(end - start)?))
+    }
+
+    fn uncook_raw<P: Position>(
+        &self,
+        span: Span<Cooked, P>,
+        input: &str,
+    ) -> Result<Span<Raw<RawFormat>, P>, RawError> {
+        let (start, end) = check_str(&span, input)?;
+        let start_char = input[..start].chars().count();
+        let len_chars = input[start..end].chars().count();
+        Ok(Span::new(raw_pos(start_char)?, raw_pos(len_chars)?))
+    }
+}
+
+impl RenderInput for RawFormat {
+    fn render_input<'a>(&self, input: &'a str) -> Cow<'a, [u8]> {
+        Cow::Borrowed(input.as_bytes())
+    }
+}
+
+/// Text whose spans are already byte offsets: v1's `CookedFormat`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CookedFormat;
+
+impl Format for CookedFormat {
+    type SpanType = Cooked;
+    type Input<'input> = str;
+
+    fn validate_span<P: Position>(
+        &self,
+        span: &Span<Cooked, P>,
+        input: &str,
+    ) -> Result<(), SpanError> {
+        span.validate(input)
+    }
+}
+
+impl RenderInput for CookedFormat {
+    fn render_input<'a>(&self, input: &'a str) -> Cow<'a, [u8]> {
+        Cow::Borrowed(input.as_bytes())
+    }
+}
+
+/// A generic function that uses the [`ToCooked`] trait with input.
+///
+/// With debug assertions enabled, every span is checked with [`Format::validate_span`]
+/// before it is cooked, and an invalid one panics.
+///
+/// # Panics
+///
+/// If the span can't be cooked against `input`; see [`try_process_span`].
+pub fn process_span<'input, F: Format, P: Position>(
+    format: &F,
+    span: Span<F::SpanType, P>,
+    input: &F::Input<'input>,
+) -> Span<Cooked, P> {
+    debug_validate(format, &span, input);
+    try_process_span(format, span, input).unwrap_or_else(|e| panic!("failed to cook span: {e}"))
+}
+
+/// With debug assertions enabled, panic if `span` fails [`Format::validate_span`], as
+/// [`process_span`] does
+pub(crate) fn debug_validate<F: Format, P: Position>(
+    format: &F,
+    span: &Span<F::SpanType, P>,
+    input: &F::Input<'_>,
+) {
+    if cfg!(debug_assertions) {
+        if let Err(e) = format.validate_span(span, input) {
+            panic!("process_span given an invalid span: {e}");
+        }
+    }
+}
+
+/// [`process_span`], reporting a span that doesn't fit the input instead of panicking.
+///
+/// For formats whose spans are already cooked ([`Coordinate::COOK_IS_IDENTITY`]), the span
+/// is returned as is without going through the format's conversion.
+pub fn try_process_span<'input, F: Format, P: Position>(
+    format: &F,
+    span: Span<F::SpanType, P>,
+    input: &F::Input<'input>,
+) -> Result<Span<Cooked, P>, CookError> {
+    if <F::SpanType as Coordinate>::COOK_IS_IDENTITY {
+        // Decided at compile time: only `Cooked` is an identity coordinate
+        return Ok(Span::new(span.start, span.len));
+    }
+    span.try_to_cooked(format, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uncook::ToRaw;
+
+    const ARGS: &[&str] = &["--foo", "bar", "baz"];
+
+    fn cook(start: Pos, len: Pos) -> Result<Span<Cooked>, CookError> {
+        Span::<Raw<CliFormat>>::new(start, len).try_to_cooked(&CliFormat, ARGS)
+    }
+
+    #[test]
+    fn cooks_multi_arg_spans() {
+        assert_eq!(cook(0, 1), Ok(Span::new(0, 5)));
+        assert_eq!(cook(1, 2), Ok(Span::new(6, 7)));
+        assert_eq!(cook(0, 3), Ok(Span::new(0, 13)));
+    }
+
+    #[test]
+    fn cooks_points_between_args() {
+        assert_eq!(cook(0, 0), Ok(Span::point(0)));
+        assert_eq!(cook(1, 0), Ok(Span::point(5)));
+        assert_eq!(cook(3, 0), Ok(Span::point(13)));
+    }
+
+    #[test]
+    fn reports_spans_past_the_args() {
+        assert_eq!(
+            cook(4, 0),
+            Err(CookError::Span(SpanError::StartOutOfRange {
+                start: 4,
+                input_len: 3
+            }))
+        );
+        assert_eq!(
+            cook(2, 2),
+            Err(CookError::Span(SpanError::EndOutOfRange {
+                end: 4,
+                input_len: 3
+            }))
+        );
+        let error = cook(2, 2).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "span ends at 4, past the end of the input (3)"
+        );
+        assert_eq!(cook(1, Pos::MAX), Err(CookError::Span(SpanError::Overflow)));
+    }
+
+    #[test]
+    fn cooks_chars_to_bytes() {
+        let span = Span::<Raw<RawFormat>>::new(1, 2);
+        assert_eq!(span.to_cooked(&RawFormat, "h\u{e9}llo"), Span::new(1, 3));
+    }
+
+    #[test]
+    fn processes_cooked_spans_as_is() {
+        let input: &[u8] = br#"{"a": true}"#;
+        let span = Span::<Cooked>::new(6, 4);
+        assert_eq!(process_span(&JsonFormat, span, input), span);
+    }
+
+    /// Cook through nothing but the [`Format`] contract
+    fn cook_in<F: Format>(format: &F, span: Span<F::SpanType>, input: &F::Input<'_>) -> Span {
+        process_span(format, span, input)
+    }
+
+    #[test]
+    fn raw_spans_are_tagged_with_their_format() {
+        use core::any::TypeId;
+        let cli = TypeId::of::<Span<Raw<CliFormat>>>();
+        assert_ne!(cli, TypeId::of::<Span<Raw<OwnedCliFormat>>>());
+        assert_ne!(cli, TypeId::of::<Span<Raw<RawFormat>>>());
+        // The same positions, in three coordinate systems
+        let owned: Vec<String> = ARGS.iter().map(|&arg| arg.into()).collect();
+        assert_eq!(cook_in(&CliFormat, Span::new(1, 1), ARGS), Span::new(6, 3));
+        assert_eq!(
+            cook_in(&OwnedCliFormat, Span::new(1, 1), &owned),
+            Span::new(6, 3)
+        );
+        assert_eq!(
+            cook_in(&RawFormat, Span::new(1, 1), "h\u{e9}llo"),
+            Span::new(1, 2)
+        );
+    }
+
+    #[test]
+    fn raw_format_walks_the_chars() {
+        let text = "\u{1f600}a\u{e9}";
+        let cook =
+            |start, len| Span::<Raw<RawFormat>>::new(start, len).try_to_cooked(&RawFormat, text);
+        assert_eq!(cook(0, 1), Ok(Span::new(0, 4)));
+        assert_eq!(cook(1, 2), Ok(Span::new(4, 3)));
+        assert_eq!(cook(3, 0), Ok(Span::point(7)));
+        assert!(cook(2, 2).is_err());
+        assert!(cook(4, 0).is_err());
+    }
+
+    #[test]
+    fn cooked_format_spans_are_already_cooked() {
+        let span = Span::<Cooked>::new(1, 2);
+        assert_eq!(span.to_cooked(&CookedFormat, "abc"), span);
+        assert_eq!(process_span(&CookedFormat, span, "abc"), span);
+    }
+
+    #[cfg(feature = "assume-coordinate")]
+    #[test]
+    fn assume_coordinate_only_relabels() {
+        // Right for ASCII, where chars and bytes agree, and wrong otherwise
+        let span = Span::<Raw<RawFormat>>::new(1, 1);
+        assert_eq!(span.assume_coordinate::<Cooked>(), Span::new(1, 1));
+        assert_ne!(span.to_cooked(&RawFormat, "\u{e9}a"), Span::new(1, 1));
+    }
+
+    /// Compiles only if the borrow and the data inside the input can have different lifetimes
+    fn cook_second<'a, 'b: 'a>(args: &'a [&'b str]) -> Span {
+        Span::<Raw<CliFormat>>::new(1, 1).to_cooked(&CliFormat, args)
+    }
+
+    #[test]
+    fn input_borrow_and_data_lifetimes_are_separate() {
+        // A brief borrow of `'static` args
+        let args: Vec<&'static str> = ARGS.to_vec();
+        assert_eq!(cook_second(&args), Span::new(6, 3));
+        // Args borrowed from a local, in a longer-lived slice
+        let local = String::from("--level=9");
+        let args = [local.as_str(), "x"];
+        assert_eq!(cook_second(&args), Span::new(10, 1));
+        assert_eq!(
+            process_span(&CliFormat, Span::<Raw<CliFormat>>::new(0, 1), &args[..]),
+            Span::new(0, 9)
+        );
+    }
+
+    /// A raw format outside the CLI ones: spans count lines
+    struct LineFormat;
+
+    impl Format for LineFormat {
+        type SpanType = Raw<LineFormat>;
+        type Input<'input> = str;
+    }
+
+    impl CookRaw for LineFormat {
+        fn cook_raw<P: Position>(
+            &self,
+            span: Span<Raw<LineFormat>, P>,
+            input: &str,
+        ) -> Result<Span<Cooked, P>, CookError> {
+            // Where each line starts, and the end of the input
+            let starts: Vec<usize> = [0]
+                .into_iter()
+                .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+                .chain([input.len() + 1])
+                .collect();
+            let (first, end) = check_bounds(&span, starts.len() - 1)?;
+            let end = (starts[end] - 1).max(starts[first]);
+            Ok(Span::new(
+                cooked_pos(starts[first])?,
+                cooked_pos(end - starts[first])?,
+            ))
+        }
+    }
+
+    #[test]
+    fn raw_formats_bring_their_own_conversion() {
+        let input = "one\ntwo\nthree";
+        let lines = |start, len| Span::<Raw<LineFormat>>::new(start, len);
+        assert_eq!(lines(1, 1).to_cooked(&LineFormat, input), Span::new(4, 3));
+        assert_eq!(lines(1, 2).to_cooked(&LineFormat, input), Span::new(4, 9));
+        assert_eq!(
+            process_span(&LineFormat, lines(0, 1), input),
+            Span::new(0, 3)
+        );
+        assert!(lines(2, 2).try_to_cooked(&LineFormat, input).is_err());
+        // Converting back is opt-in too
+        let back = Span::<Cooked>::new(4, 3).try_to_raw(&LineFormat, input);
+        assert_eq!(back, Err(RawError::Unsupported));
+    }
+
+    /// Generic code over any format, with no `ToCooked` or `ToRaw` bounds of its own
+    fn round_trip<F: Format>(
+        format: &F,
+        span: Span<F::SpanType>,
+        input: &F::Input<'_>,
+    ) -> (Span, Result<Span<F::SpanType>, RawError>) {
+        let cooked = span.to_cooked(format, input);
+        (cooked, cooked.try_to_raw(format, input))
+    }
+
+    #[test]
+    fn format_implies_the_conversions() {
+        let span = Span::new(1, 2);
+        assert_eq!(
+            round_trip(&CliFormat, span, ARGS),
+            (Span::new(6, 7), Ok(span))
+        );
+        let span = Span::new(1, 1);
+        assert_eq!(
+            round_trip(&RawFormat, span, "h\u{e9}"),
+            (Span::new(1, 2), Ok(span))
+        );
+        let span = Span::new(2, 3);
+        assert_eq!(round_trip(&JsonFormat, span, b"[1, 2]"), (span, Ok(span)));
+    }
+}
@@ -0,0 +1,20 @@
+//! JSON documents: the input [`JsonFormat`](crate::JsonFormat) spans point into.
+//!
+//! JSON spans are byte offsets into the document, so everything here produces
+//! [`Cooked`](crate::Cooked) spans directly.
+
+mod diagnostic;
+mod duplicates;
+mod error;
+mod parser;
+mod pointer;
+mod string;
+mod token;
+
+pub use diagnostic::{Diagnostic, Found, JsonError, Label};
+pub use duplicates::duplicate_keys;
+pub use error::JsonCookError;
+pub use parser::{parse, Member, Parsed, Value};
+pub use pointer::{JsonPointer, PointerError, Target};
+pub use string::{Decoded, DecodedString, StringSpan};
+pub use token::{tokenize, LexError, Token, Tokens};
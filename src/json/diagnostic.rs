@@ -0,0 +1,226 @@
+//! Problems found in a JSON document, and rendering them against it.
+
+use core::fmt;
+
+use super::{LexError, Token};
+use crate::parse::LineCol;
+use crate::pos::Position;
+use crate::render::{render_marked, RenderInput};
+use crate::source::Source;
+use crate::span::{Cooked, Span};
+
+/// What the parser found where it expected something else
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Found {
+    /// A token
+    Token(Token),
+    /// The end of the document
+    EndOfInput,
+}
+
+impl fmt::Display for Found {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Found::Token(token) => token.fmt(f),
+            Found::EndOfInput => f.write_str("the end of the input"),
+        }
+    }
+}
+
+/// A problem in a JSON document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JsonError {
+    /// Input that isn't a valid token
+    Lex(LexError),
+    /// A backslash escape that JSON doesn't define, or a lone UTF-16 surrogate
+    InvalidEscape,
+    /// An unescaped control character (such as a tab) inside a string
+    ControlCharacter,
+    /// Bytes inside a string that aren't UTF-8
+    InvalidUtf8,
+    /// Something other than what the grammar allows here
+    Unexpected {
+        /// What would have been allowed, e.g. "`,` or `}`"
+        expected: &'static str,
+        /// What was there instead
+        found: Found,
+    },
+    /// A comma after the last element of an array or member of an object
+    TrailingComma,
+    /// Tokens after the end of the top-level value
+    TrailingContent,
+    /// A key an object already has; see [`duplicate_keys`](super::duplicate_keys)
+    DuplicateKey,
+    /// A document too long for its spans to address, which isn't parsed
+    TooLarge,
+    /// An array or object nested too deeply to parse, which is skipped
+    TooDeep,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Lex(e) => e.fmt(f),
+            JsonError::InvalidEscape => f.write_str("invalid escape in string"),
+            JsonError::ControlCharacter => f.write_str("control character in string"),
+            JsonError::InvalidUtf8 => f.write_str("invalid UTF-8 in string"),
+            JsonError::Unexpected { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            JsonError::TrailingComma => f.write_str("trailing comma"),
+            JsonError::TrailingContent => f.write_str("unexpected content after the document"),
+            JsonError::DuplicateKey => f.write_str("duplicate key"),
+            JsonError::TooLarge => f.write_str("document too large for 32-bit spans"),
+            JsonError::TooDeep => f.write_str("nested too deeply"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// A [`JsonError`], and the span of the document it is about
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    /// What is wrong
+    pub error: JsonError,
+    /// Where it is
+    pub span: Span<Cooked>,
+    /// Another place the error involves, such as where a duplicate key was first defined
+    pub secondary: Option<Label>,
+}
+
+/// A secondary span of a [`Diagnostic`], and what is there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label {
+    /// Shown as a note above the span, e.g. "first defined here"
+    pub message: &'static str,
+    /// The span
+    pub span: Span<Cooked>,
+}
+
+impl Diagnostic {
+    /// A diagnostic with no secondary span
+    pub fn new(error: JsonError, span: Span<Cooked>) -> Self {
+        Self {
+            error,
+            span,
+            secondary: None,
+        }
+    }
+
+    /// The error, its position, and the line it is on with the span underlined with `^`, in
+    /// the style of [`render_span`](crate::render_span). A secondary span is underlined with `-` under a note:
+    /// on the same copy of the line if it is on the same line, or under its own copy.
+    ///
+    /// ```text
+    /// error: duplicate key
+    ///  --> config.json:3:3
+    /// Input:   "port": 8080
+    /// Span:    ^^^^^^
+    /// note: first defined here
+    ///  --> config.json:2:3
+    /// Input:   "port": 80,
+    /// Span:    ------
+    /// ```
+    pub fn render<F: RenderInput>(&self, source: &Source<'_, '_, F>) -> String {
+        let text = source.text();
+        let name = source.name();
+        let primary = Snippet::new(text, self.span);
+        let mut rendered = format!("error: {}\n --> {name}:{}\n", self.error, primary.location);
+        let Some(label) = &self.secondary else {
+            return rendered + &render_marked(primary.line, &[(primary.range, '^')]);
+        };
+        let secondary = Snippet::new(text, label.span);
+        let note = format!(
+            "note: {}\n --> {name}:{}",
+            label.message, secondary.location
+        );
+        if secondary.line_start == primary.line_start {
+            let ranges = [(primary.range, '^'), (secondary.range, '-')];
+            rendered += &format!("{}\n{note}", render_marked(primary.line, &ranges));
+        } else {
+            rendered += &format!(
+                "{}\n{note}\n{}",
+                render_marked(primary.line, &[(primary.range, '^')]),
+                render_marked(secondary.line, &[(secondary.range, '-')])
+            );
+        }
+        rendered
+    }
+}
+
+/// The line a span starts on, and where the span is in it
+struct Snippet<'a> {
+    /// The line, without its line break
+    line: &'a [u8],
+    /// Where the line starts in the text
+    line_start: usize,
+    /// The span's bytes within the line, cut off at the end of the line
+    range: (usize, usize),
+    location: LineCol,
+}
+
+impl<'a> Snippet<'a> {
+    fn new(text: &'a [u8], span: Span<Cooked>) -> Self {
+        let start = span.start.to_usize().unwrap_or(usize::MAX).min(text.len());
+        let end = span.end().to_usize().unwrap_or(usize::MAX).min(text.len());
+        let line_start = text[..start]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let line_end = text[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(text.len(), |i| start + i);
+        let line = &text[line_start..line_end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        // A span past a stripped `\r` starts beyond the line, so pull it back to the line's end
+        let line_end = line_start + line.len();
+        let in_line_start = start.min(line_end);
+        let in_line_end = end.clamp(in_line_start, line_end);
+        let location = LineCol {
+            line: text[..line_start].iter().filter(|&&b| b == b'\n').count() + 1,
+            col: String::from_utf8_lossy(&text[line_start..start])
+                .chars()
+                .count()
+                + 1,
+        };
+        Self {
+            line,
+            line_start,
+            range: (in_line_start - line_start, in_line_end - line_start),
+            location,
+        }
+    }
+}
+
+/// Shown as `{error} at {span}`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.error, self.span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::parse;
+    use crate::JsonFormat;
+
+    #[test]
+    fn renders_a_span_after_a_carriage_return() {
+        let input = b"[1\r";
+        let parsed = parse(input);
+        let diagnostic = &parsed.diagnostics[0];
+        assert_eq!(diagnostic.span, Span::point(3));
+        let source = Source::new("crlf.json", JsonFormat, input);
+        let rendered = diagnostic.render(&source);
+        assert_eq!(
+            rendered,
+            "error: expected `]`, found the end of the input\n \
+             --> crlf.json:1:4\n\
+             Input: [1\n\
+             Span:    <EOF>"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Finding keys defined more than once in the same object.
+
+use std::collections::HashMap;
+
+use super::{Diagnostic, JsonError, Label, Value};
+use crate::span::Spanned;
+
+/// Every key that repeats an earlier key of the same object, at any depth, in document order.
+///
+/// Most JSON parsers keep the last of a repeated key without a word, so a duplicate is
+/// usually a mistake. Each is a [`JsonError::DuplicateKey`] at the repeated key, with the key's
+/// first definition as its secondary span. Keys are compared after decoding escapes, so
+/// `"a"` and `"\u0061"` are the same key.
+pub fn duplicate_keys(value: &Spanned<Value>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check(value, &mut diagnostics);
+    diagnostics
+}
+
+fn check(value: &Spanned<Value>, diagnostics: &mut Vec<Diagnostic>) {
+    match &value.node {
+        Value::Object(members) => {
+            let mut first = HashMap::new();
+            for member in members {
+                let key = &member.key;
+                if let Some(&first) = first.get(key.node.as_str()) {
+                    diagnostics.push(Diagnostic {
+                        error: JsonError::DuplicateKey,
+                        span: key.span,
+                        secondary: Some(Label {
+                            message: "first defined here",
+                            span: first,
+                        }),
+                    });
+                } else {
+                    first.insert(key.node.as_str(), key.span);
+                }
+                check(&member.value, diagnostics);
+            }
+        }
+        Value::Array(elements) => {
+            for element in elements {
+                check(element, diagnostics);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::JsonFormat;
+    use crate::json::parse;
+    use crate::source::Source;
+    use crate::span::Span;
+
+    const INPUT: &str = "{\"a\": 1, \"b\": [{\"x\": 1, \"x\": 2}],\n \"a\": 3, \"\\u0061\": 4}";
+
+    fn duplicates() -> Vec<Diagnostic> {
+        duplicate_keys(&parse(INPUT.as_bytes()).value)
+    }
+
+    #[test]
+    fn finds_duplicates_at_any_depth() {
+        let found: Vec<_> = duplicates()
+            .iter()
+            .map(|d| (&INPUT[d.span], &INPUT[d.secondary.unwrap().span]))
+            .collect();
+        let escaped_a = r#""\u0061""#;
+        assert_eq!(
+            found,
+            [
+                (r#""x""#, r#""x""#),
+                (r#""a""#, r#""a""#),
+                (escaped_a, r#""a""#)
+            ]
+        );
+        assert!(duplicates()
+            .iter()
+            .all(|d| d.error == JsonError::DuplicateKey));
+        // The secondary span is the first definition
+        assert_eq!(duplicates()[1].secondary.unwrap().span, Span::new(1, 3));
+    }
+
+    /// The rendering without the spaces padding out each underline
+    fn trim_lines(rendered: &str) -> String {
+        let lines: Vec<_> = rendered.lines().map(str::trim_end).collect();
+        lines.join("\n")
+    }
+
+    #[test]
+    fn renders_both_locations() {
+        let source = Source::new("config.json", JsonFormat, INPUT.as_bytes());
+        let same_line = trim_lines(&duplicates()[0].render(&source));
+        assert_eq!(
+            same_line,
+            "error: duplicate key\n \
+             --> config.json:1:25\n\
+             Input: {\"a\": 1, \"b\": [{\"x\": 1, \"x\": 2}],\n\
+             Span:                  ---     ^^^\n\
+             note: first defined here\n \
+             --> config.json:1:17"
+        );
+        let other_line = trim_lines(&duplicates()[1].render(&source));
+        assert_eq!(
+            other_line,
+            "error: duplicate key\n \
+             --> config.json:2:2\n\
+             Input:  \"a\": 3, \"\\u0061\": 4}\n\
+             Span:   ^^^\n\
+             note: first defined here\n \
+             --> config.json:1:2\n\
+             Input: {\"a\": 1, \"b\": [{\"x\": 1, \"x\": 2}],\n\
+             Span:   ---"
+        );
+    }
+}
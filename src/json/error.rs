@@ -0,0 +1,46 @@
+//! Why a span into a JSON document can't be cooked.
+
+use core::fmt;
+
+use super::PointerError;
+use crate::format::CookError;
+use crate::validate::SpanError;
+
+/// Why a [`StringSpan`](super::StringSpan) or a [`JsonPointer`](super::JsonPointer) can't be
+/// cooked against a document: the ways any span can fail, and the ways only these can
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonCookError {
+    /// The span doesn't fit the document, or its position width
+    Cook(CookError),
+    /// A span that should be a JSON string token isn't one
+    NotAString {
+        /// Start of the span, as a byte offset
+        start: u64,
+    },
+    /// A JSON pointer doesn't point at anything in the document, and what is missing
+    NoSuchPath(PointerError),
+}
+
+impl fmt::Display for JsonCookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonCookError::Cook(e) => e.fmt(f),
+            JsonCookError::NotAString { start } => write!(f, "no JSON string starts at {start}"),
+            JsonCookError::NoSuchPath(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for JsonCookError {}
+
+impl From<CookError> for JsonCookError {
+    fn from(e: CookError) -> Self {
+        JsonCookError::Cook(e)
+    }
+}
+
+impl From<SpanError> for JsonCookError {
+    fn from(e: SpanError) -> Self {
+        JsonCookError::Cook(e.into())
+    }
+}
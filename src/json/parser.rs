@@ -0,0 +1,448 @@
+//! Parsing a JSON document into a tree of spanned values, recovering from errors.
+
+use super::string::unescape;
+use super::{tokenize, Diagnostic, Found, JsonError, Token};
+use crate::pos::{Pos, Position};
+use crate::span::{Cooked, Span, Spannable, Spanned};
+
+/// A JSON value whose children carry their spans
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Bool(bool),
+    /// A number
+    Number(f64),
+    /// A string, with its escapes decoded
+    String(String),
+    /// An array
+    Array(Vec<Spanned<Value>>),
+    /// An object, with its members in document order (duplicate keys included)
+    Object(Vec<Member>),
+    /// Stands in for a value that couldn't be parsed, which has a [`Diagnostic`]
+    Error,
+}
+
+/// A `"key": value` member of an object
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    /// The key, decoded, with the span of the string token (quotes included)
+    pub key: Spanned<String>,
+    /// The value
+    pub value: Spanned<Value>,
+}
+
+impl Member {
+    /// The span from the start of the key to the end of the value
+    pub fn span(&self) -> Span<Cooked> {
+        self.key.span.join(self.value.span)
+    }
+}
+
+/// The result of [`parse`]: the document's value, and every problem found in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parsed {
+    /// The top-level value, with [`Value::Error`] wherever a value couldn't be parsed
+    pub value: Spanned<Value>,
+    /// The problems found, in document order
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Parse a JSON document, carrying on past errors.
+///
+/// Trailing commas, missing colons and commas, unterminated strings and stray tokens are
+/// reported as [`Diagnostic`]s, and the parser resynchronises at the next token that makes
+/// sense, so one run finds every problem. Spans are byte offsets into `input`.
+///
+/// Arrays and objects nested more than 128 deep are reported as [`JsonError::TooDeep`] and
+/// skipped, so a hostile document can't overflow the stack.
+///
+/// A document longer than [`Pos`] can address is reported as [`JsonError::TooLarge`] rather
+/// than parsed; [`tokenize`] can still read it with `u64` spans.
+pub fn parse(input: &[u8]) -> Parsed {
+    let Ok(tokens) = tokenize::<Pos>(input) else {
+        let start = Span::point(0);
+        return Parsed {
+            value: Value::Error.with_span(start),
+            diagnostics: vec![Diagnostic::new(JsonError::TooLarge, start)],
+        };
+    };
+    let mut parser = Parser {
+        input,
+        tokens: tokens.filter(|t| !t.node.is_trivia()).collect(),
+        pos: 0,
+        depth: 0,
+        diagnostics: Vec::new(),
+    };
+    let value = parser.value();
+    if let Some(rest) = Span::cover(parser.tokens[parser.pos..].iter().map(|t| t.span)) {
+        parser.report(JsonError::TrailingContent, rest);
+    }
+    Parsed {
+        value,
+        diagnostics: parser.diagnostics,
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    /// The tokens, without whitespace
+    tokens: Vec<Spanned<Token>>,
+    /// Index of the next token
+    pos: usize,
+    /// How many arrays and objects the next token is inside
+    depth: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// How deep arrays and objects can nest before [`JsonError::TooDeep`]
+const MAX_DEPTH: usize = 128;
+
+impl Parser<'_> {
+    /// The next token and its span, or a point at the end of the input
+    fn peek(&self) -> (Found, Span<Cooked>) {
+        match self.tokens.get(self.pos) {
+            Some(token) => (Found::Token(token.node), token.span),
+            None => (Found::EndOfInput, Span::<Cooked>::end_of_input(self.input)),
+        }
+    }
+
+    fn bump(&mut self) {
+        self.pos += 1;
+    }
+
+    fn report(&mut self, error: JsonError, span: Span<Cooked>) {
+        self.diagnostics.push(Diagnostic::new(error, span));
+    }
+
+    /// Report `found` where `expected` should be. An invalid token is reported as what is
+    /// wrong with it instead, which is more use.
+    fn unexpected(&mut self, expected: &'static str, found: Found, span: Span<Cooked>) {
+        match found {
+            Found::Token(Token::Error(e)) => self.report(JsonError::Lex(e), span),
+            _ => self.report(JsonError::Unexpected { expected, found }, span),
+        }
+    }
+
+    fn value(&mut self) -> Spanned<Value> {
+        let (found, span) = self.peek();
+        let Found::Token(token) = found else {
+            self.unexpected("a value", found, span);
+            return Value::Error.with_span(span);
+        };
+        let value = match token {
+            Token::BeginObject | Token::BeginArray if self.depth == MAX_DEPTH => {
+                return self.too_deep(span);
+            }
+            Token::BeginObject | Token::BeginArray => {
+                self.depth += 1;
+                let value = if token == Token::BeginObject {
+                    self.object(span)
+                } else {
+                    self.array(span)
+                };
+                self.depth -= 1;
+                return value;
+            }
+            Token::String => Value::String(self.string(span)),
+            Token::Number => {
+                let text = core::str::from_utf8(&self.input[span]).unwrap_or_default();
+                Value::Number(text.parse().unwrap_or(f64::NAN))
+            }
+            Token::True => Value::Bool(true),
+            Token::False => Value::Bool(false),
+            Token::Null => Value::Null,
+            Token::Error(_) => {
+                self.unexpected("a value", found, span);
+                Value::Error
+            }
+            _ => {
+                // Leave the token for the enclosing array or object to deal with
+                self.unexpected("a value", found, span);
+                return Value::Error.with_span(Span::point(span.start));
+            }
+        };
+        self.bump();
+        value.with_span(span)
+    }
+
+    /// Report the array or object opening at `open` as too deep, and skip to its end
+    fn too_deep(&mut self, open: Span<Cooked>) -> Spanned<Value> {
+        self.report(JsonError::TooDeep, open);
+        let mut span = open;
+        let mut nesting = 0_usize;
+        while let Some(&Spanned { node, span: at }) = self.tokens.get(self.pos) {
+            span = span.join(at);
+            self.bump();
+            match node {
+                Token::BeginObject | Token::BeginArray => nesting += 1,
+                Token::EndObject | Token::EndArray => nesting -= 1,
+                _ => {}
+            }
+            if nesting == 0 {
+                break;
+            }
+        }
+        Value::Error.with_span(span)
+    }
+
+    /// Decode the string token at `span`, reporting any bad escapes in it
+    fn string(&mut self, span: Span<Cooked>) -> String {
+        let mut errors = Vec::new();
+        let (decoded, _) = unescape(&self.input[span], |start, end, e| {
+            errors.push((start, end, e))
+        });
+        for (start, end, error) in errors {
+            let pos = |n: usize| Pos::from_usize(n).unwrap_or(Pos::MAX);
+            self.report(error, Span::new(span.start + pos(start), pos(end - start)));
+        }
+        decoded
+    }
+
+    fn array(&mut self, open: Span<Cooked>) -> Spanned<Value> {
+        self.bump();
+        let mut elements = Vec::new();
+        let mut span = open;
+        loop {
+            let (found, at) = self.peek();
+            match found {
+                Found::Token(Token::EndArray) => {
+                    self.bump();
+                    span = span.join(at);
+                    break;
+                }
+                Found::EndOfInput => {
+                    self.unexpected("`]`", found, at);
+                    break;
+                }
+                Found::Token(token) if !starts_value(token) => {
+                    // e.g. `[1,,2]`: skip the token rather than reporting it again below
+                    self.unexpected("a value", found, at);
+                    self.bump();
+                    span = span.join(at);
+                    continue;
+                }
+                Found::Token(_) => {
+                    let element = self.value();
+                    span = span.join(element.span);
+                    elements.push(element);
+                }
+            }
+            if !self.separator(Token::EndArray, "`,` or `]`", &mut span) {
+                break;
+            }
+        }
+        Value::Array(elements).with_span(span)
+    }
+
+    fn object(&mut self, open: Span<Cooked>) -> Spanned<Value> {
+        self.bump();
+        let mut members = Vec::new();
+        let mut span = open;
+        loop {
+            let (found, at) = self.peek();
+            match found {
+                Found::Token(Token::EndObject) => {
+                    self.bump();
+                    span = span.join(at);
+                    break;
+                }
+                Found::EndOfInput => {
+                    self.unexpected("`}`", found, at);
+                    break;
+                }
+                Found::Token(Token::String) => {
+                    let member = self.member(at);
+                    span = span.join(member.span());
+                    members.push(member);
+                }
+                Found::Token(_) => {
+                    // e.g. an unquoted key: skip it, and its value if it has one
+                    self.unexpected("a string key", found, at);
+                    self.bump();
+                    span = span.join(at);
+                    if self.peek().0 == Found::Token(Token::Colon) {
+                        self.bump();
+                        span = span.join(self.value().span);
+                    }
+                }
+            }
+            if !self.separator(Token::EndObject, "`,` or `}`", &mut span) {
+                break;
+            }
+        }
+        Value::Object(members).with_span(span)
+    }
+
+    /// A member, starting at the key string token at `key`
+    fn member(&mut self, key: Span<Cooked>) -> Member {
+        let key = self.string(key).with_span(key);
+        self.bump();
+        let (found, at) = self.peek();
+        if found == Found::Token(Token::Colon) {
+            self.bump();
+        } else {
+            self.unexpected("`:`", found, at);
+            // With neither a colon nor a value, the value is missing too: don't report that
+            if !matches!(found, Found::Token(token) if starts_value(token)) {
+                let value = Value::Error.with_span(Span::point(at.start));
+                return Member { key, value };
+            }
+        }
+        let value = self.value();
+        Member { key, value }
+    }
+
+    /// After an element of an array or object: consume a comma, and report one that is
+    /// trailing. Returns false once the array or object is over.
+    fn separator(&mut self, close: Token, expected: &'static str, span: &mut Span<Cooked>) -> bool {
+        let (found, at) = self.peek();
+        match found {
+            Found::Token(Token::Comma) => {
+                self.bump();
+                *span = span.join(at);
+                if self.peek().0 == Found::Token(close) {
+                    self.report(JsonError::TrailingComma, at);
+                }
+                true
+            }
+            Found::Token(token) if token == close => true,
+            Found::EndOfInput => true,
+            // The wrong closing bracket: assume it closes an enclosing array or object
+            Found::Token(Token::EndArray | Token::EndObject) => {
+                self.unexpected(expected, found, at);
+                false
+            }
+            // Left for the next element to report, e.g. an unquoted key
+            Found::Token(Token::Error(_)) => true,
+            // Probably a missing comma: carry on with this as the next element
+            Found::Token(token) if starts_value(token) => {
+                self.unexpected(expected, found, at);
+                true
+            }
+            Found::Token(_) => {
+                self.unexpected(expected, found, at);
+                self.bump();
+                *span = span.join(at);
+                true
+            }
+        }
+    }
+}
+
+/// Whether `token` can start a value. Invalid tokens count, so they're reported as such.
+fn starts_value(token: Token) -> bool {
+    !matches!(
+        token,
+        Token::EndObject | Token::EndArray | Token::Colon | Token::Comma | Token::Whitespace
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::LexError;
+
+    /// Every diagnostic for `input`, with the text it points at
+    fn problems(input: &str) -> Vec<(JsonError, &str)> {
+        let parsed = parse(input.as_bytes());
+        parsed
+            .diagnostics
+            .iter()
+            .map(|d| (d.error, &input[d.span]))
+            .collect()
+    }
+
+    fn unexpected(expected: &'static str, found: Token) -> JsonError {
+        JsonError::Unexpected {
+            expected,
+            found: Found::Token(found),
+        }
+    }
+
+    #[test]
+    fn parses_with_spans() {
+        let input = r#"{"a": [1, true], "b": {"c": null}}"#;
+        let parsed = parse(input.as_bytes());
+        assert!(parsed.diagnostics.is_empty());
+        let Value::Object(members) = &parsed.value.node else {
+            panic!("not an object: {:?}", parsed.value);
+        };
+        assert_eq!(members[0].key.node, "a");
+        assert_eq!(&input[members[0].value.span], "[1, true]");
+        assert_eq!(&input[members[1].span()], r#""b": {"c": null}"#);
+    }
+
+    #[test]
+    fn trailing_comma() {
+        assert_eq!(problems("[1,]"), [(JsonError::TrailingComma, ",")]);
+        assert_eq!(problems(r#"{"a": 1,}"#), [(JsonError::TrailingComma, ",")]);
+        let parsed = parse(b"[1,]");
+        assert!(matches!(&parsed.value.node, Value::Array(elements) if elements.len() == 1));
+    }
+
+    #[test]
+    fn missing_colon() {
+        assert_eq!(
+            problems(r#"{"a" 1}"#),
+            [(unexpected("`:`", Token::Number), "1")]
+        );
+        // The value is still parsed
+        let parsed = parse(br#"{"a" 1}"#);
+        let Value::Object(members) = &parsed.value.node else {
+            panic!("not an object: {:?}", parsed.value);
+        };
+        assert_eq!(members[0].value.node, Value::Number(1.0));
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let input = "[\"abc\n, 1]";
+        let unterminated = JsonError::Lex(LexError::UnterminatedString);
+        assert_eq!(problems(input), [(unterminated, "\"abc")]);
+        let parsed = parse(input.as_bytes());
+        let Value::Array(elements) = &parsed.value.node else {
+            panic!("not an array: {:?}", parsed.value);
+        };
+        assert_eq!(elements[1].node, Value::Number(1.0));
+    }
+
+    #[test]
+    fn stray_tokens() {
+        assert_eq!(
+            problems("[1 : 2]"),
+            [(unexpected("`,` or `]`", Token::Colon), ":")]
+        );
+        assert_eq!(
+            problems("[1,,2]"),
+            [(unexpected("a value", Token::Comma), ",")]
+        );
+        assert_eq!(
+            problems(r#"{a: 1, "b": 2}"#),
+            [(JsonError::Lex(LexError::InvalidLiteral), "a")]
+        );
+        assert_eq!(
+            problems(r#"{"a": [1}"#),
+            [(unexpected("`,` or `]`", Token::EndObject), "}")]
+        );
+        assert_eq!(problems("[1] 2"), [(JsonError::TrailingContent, "2")]);
+    }
+
+    #[test]
+    fn deep_nesting_is_skipped() {
+        let deep = "[".repeat(50_000) + &"]".repeat(50_000);
+        let input = format!(r#"{{"deep": {deep}, "next": 1}}"#);
+        let diagnostics = parse(input.as_bytes()).diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].error, JsonError::TooDeep);
+        // The object counts towards the limit
+        assert_eq!(diagnostics[0].span, Span::new(9 + 127, 1));
+
+        let parsed = parse(input.as_bytes());
+        let Value::Object(members) = &parsed.value.node else {
+            panic!("not an object: {:?}", parsed.value);
+        };
+        assert_eq!(members[1].value.node, Value::Number(1.0));
+    }
+}
@@ -0,0 +1,371 @@
+//! JSON Pointers (RFC 6901), resolved to the spans of what they point at.
+
+use core::fmt;
+use core::str::FromStr;
+
+use super::JsonCookError;
+use super::{parse, Member, Value};
+use crate::format::{JsonFormat, ToCooked};
+use crate::pos::Pos;
+use crate::span::{Cooked, Span, Spanned};
+
+/// A JSON Pointer such as `/servers/2/port`, as used by schema validators to say where a
+/// problem is.
+///
+/// It is a position in its own coordinate system: it cooks via [`ToCooked`] against
+/// [`JsonFormat`] into the span of the key, the value, or the whole member it points at (see
+/// [`Target`]), so it can be rendered like any other span. [`JsonPointer::resolve`] gives a
+/// [`PointerError`] saying which part of a pointer that doesn't resolve is missing.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonPointer {
+    text: String,
+    /// The reference tokens, unescaped, with where the `/` before each is in `text`
+    tokens: Vec<(String, usize)>,
+    target: Target,
+}
+
+/// Which part of an object member a [`JsonPointer`] cooks to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// The key's string token, quotes included
+    Key,
+    /// The value
+    #[default]
+    Value,
+    /// The key through the value. For an array element or the whole document, there is no
+    /// key, so this is the value.
+    Member,
+}
+
+impl JsonPointer {
+    /// Parse a pointer, which is either empty (the whole document) or `/`-separated reference
+    /// tokens, with `~1` for `/` and `~0` for `~`. It points at the value.
+    pub fn parse(text: &str) -> Result<Self, PointerError> {
+        let mut tokens = Vec::new();
+        if !text.is_empty() && !text.starts_with('/') {
+            return Err(PointerError::Syntax { pos: 0 });
+        }
+        let mut slash = 0;
+        for raw in text.split('/').skip(1) {
+            let mut token = String::with_capacity(raw.len());
+            let mut chars = raw.char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '~' => match chars.next() {
+                        Some((_, '0')) => token.push('~'),
+                        Some((_, '1')) => token.push('/'),
+                        _ => return Err(PointerError::Syntax { pos: slash + 1 + i }),
+                    },
+                    c => token.push(c),
+                }
+            }
+            tokens.push((token, slash));
+            slash += 1 + raw.len();
+        }
+        Ok(Self {
+            text: text.to_owned(),
+            tokens,
+            target: Target::Value,
+        })
+    }
+
+    /// Point at the key of the member instead
+    pub fn key(self) -> Self {
+        self.with_target(Target::Key)
+    }
+
+    /// Point at the whole member instead: its key through its value
+    pub fn member(self) -> Self {
+        self.with_target(Target::Member)
+    }
+
+    /// Point at `target` of the member
+    pub fn with_target(self, target: Target) -> Self {
+        Self { target, ..self }
+    }
+
+    /// What part of the member this points at
+    pub fn target(&self) -> Target {
+        self.target
+    }
+
+    /// The pointer, as written
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The reference tokens, with `~0` and `~1` decoded
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.tokens.iter().map(|(token, _)| token.as_str())
+    }
+
+    /// The span this points at in the JSON document `input`, or what is missing.
+    ///
+    /// The document is parsed with [`parse`], so a pointer into the parts of a malformed
+    /// document that could be parsed still resolves. Where an object has a key more than once,
+    /// the last one counts, as in most JSON parsers.
+    pub fn resolve(&self, input: &[u8]) -> Result<Span<Cooked>, PointerError> {
+        self.resolve_in(&parse(input).value)
+    }
+
+    /// [`JsonPointer::resolve`] against an already parsed document
+    pub fn resolve_in(&self, root: &Spanned<Value>) -> Result<Span<Cooked>, PointerError> {
+        let mut value = root;
+        let mut member: Option<&Member> = None;
+        for (token, slash) in &self.tokens {
+            let parent = &self.text[..*slash];
+            let not_found = || PointerError::NotFound {
+                parent: parent.to_owned(),
+                token: token.clone(),
+                span: value.span,
+            };
+            match &value.node {
+                Value::Object(members) => {
+                    let found = members.iter().rev().find(|m| m.key.node == *token);
+                    let found = found.ok_or_else(not_found)?;
+                    value = &found.value;
+                    member = Some(found);
+                }
+                Value::Array(elements) => {
+                    let element = index(token).and_then(|i| elements.get(i));
+                    value = element.ok_or_else(not_found)?;
+                    member = None;
+                }
+                other => {
+                    return Err(PointerError::NotAContainer {
+                        path: parent.to_owned(),
+                        found: describe(other),
+                        span: value.span,
+                    })
+                }
+            }
+        }
+        match (self.target, member) {
+            (Target::Value, _) => Ok(value.span),
+            (Target::Key, Some(member)) => Ok(member.key.span),
+            (Target::Member, Some(member)) => Ok(member.span()),
+            (Target::Member, None) => Ok(value.span),
+            (Target::Key, None) => Err(PointerError::NoKey {
+                path: self.text.clone(),
+                span: value.span,
+            }),
+        }
+    }
+}
+
+impl FromStr for JsonPointer {
+    type Err = PointerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Shown as written
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Fails with [`JsonCookError::NoSuchPath`], carrying what [`JsonPointer::resolve`] found
+/// missing
+impl<'input> ToCooked<'input, JsonFormat, Pos> for &JsonPointer {
+    type Error = JsonCookError;
+
+    fn try_to_cooked(
+        self,
+        _format: &JsonFormat,
+        input: &[u8],
+    ) -> Result<Span<Cooked>, JsonCookError> {
+        self.resolve(input).map_err(JsonCookError::NoSuchPath)
+    }
+}
+
+/// Why a [`JsonPointer`] can't be parsed, or doesn't resolve in a document
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PointerError {
+    /// The pointer doesn't start with `/`, or has a `~` not followed by `0` or `1`
+    Syntax {
+        /// Byte offset of the problem in the pointer
+        pos: usize,
+    },
+    /// An object has no member with the key, or an array no element at the index
+    NotFound {
+        /// The pointer to the object or array, as written
+        parent: String,
+        /// The key or index it doesn't have
+        token: String,
+        /// The object or array
+        span: Span<Cooked>,
+    },
+    /// The pointer goes on past a string, number, boolean or null
+    NotAContainer {
+        /// The pointer to the value, as written
+        path: String,
+        /// What it is, e.g. "a number"
+        found: &'static str,
+        /// The value
+        span: Span<Cooked>,
+    },
+    /// [`Target::Key`] for the whole document or an array element, which have no key
+    NoKey {
+        /// The pointer, as written
+        path: String,
+        /// The value it points at
+        span: Span<Cooked>,
+    },
+}
+
+impl PointerError {
+    /// The span in the document the error is about, for rendering: e.g. the object that is
+    /// missing a key
+    pub fn span(&self) -> Option<Span<Cooked>> {
+        match self {
+            PointerError::Syntax { .. } => None,
+            PointerError::NotFound { span, .. }
+            | PointerError::NotAContainer { span, .. }
+            | PointerError::NoKey { span, .. } => Some(*span),
+        }
+    }
+}
+
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointerError::Syntax { pos } => write!(
+                f,
+                "invalid JSON pointer at byte {pos}: it must start with `/`, and `~` must be \
+                 followed by `0` or `1`"
+            ),
+            PointerError::NotFound { parent, token, .. } => {
+                write!(f, "{} has no `{token}`", Path(parent))
+            }
+            PointerError::NotAContainer { path, found, .. } => {
+                write!(f, "{} is {found}, not an object or array", Path(path))
+            }
+            PointerError::NoKey { path, .. } => {
+                write!(f, "{} isn't an object member, so it has no key", Path(path))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PointerError {}
+
+/// A pointer in a message: the empty pointer is the whole document
+struct Path<'a>(&'a str);
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            "" => f.write_str("the document"),
+            path => write!(f, "`{path}`"),
+        }
+    }
+}
+
+/// An array index reference token: digits, without leading zeros
+fn index(token: &str) -> Option<usize> {
+    let digits = token.bytes().all(|b| b.is_ascii_digit());
+    if token.is_empty() || !digits || token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// A value's kind, for [`PointerError::NotAContainer`]
+fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+        Value::Error => "an invalid value",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::Source;
+
+    const INPUT: &str = r#"{"servers": [{"port": 80}, {"port": 8080}], "a/b": {"~x": 1}}"#;
+
+    fn resolve(pointer: JsonPointer) -> Result<&'static str, PointerError> {
+        pointer.resolve(INPUT.as_bytes()).map(|span| &INPUT[span])
+    }
+
+    fn pointer(text: &str) -> JsonPointer {
+        JsonPointer::parse(text).unwrap()
+    }
+
+    #[test]
+    fn resolves_values_keys_and_members() {
+        assert_eq!(resolve(pointer("")), Ok(INPUT));
+        assert_eq!(resolve(pointer("/servers/1/port")), Ok("8080"));
+        assert_eq!(resolve(pointer("/servers/1/port").key()), Ok(r#""port""#));
+        assert_eq!(
+            resolve(pointer("/servers/1/port").member()),
+            Ok(r#""port": 8080"#)
+        );
+        assert_eq!(
+            resolve(pointer("/servers/0").member()),
+            Ok(r#"{"port": 80}"#)
+        );
+        assert_eq!(resolve(pointer("/a~1b/~0x")), Ok("1"));
+    }
+
+    #[test]
+    fn reports_what_is_missing() {
+        let servers = Span::new(12, 30);
+        let missing = resolve(pointer("/servers/2")).unwrap_err();
+        assert_eq!(
+            missing,
+            PointerError::NotFound {
+                parent: "/servers".into(),
+                token: "2".into(),
+                span: servers,
+            }
+        );
+        assert_eq!(missing.to_string(), "`/servers` has no `2`");
+        assert_eq!(missing.span(), Some(servers));
+        // Array indices have no leading zeros
+        assert!(matches!(
+            resolve(pointer("/servers/01")),
+            Err(PointerError::NotFound { .. })
+        ));
+        let scalar = resolve(pointer("/servers/1/port/x")).unwrap_err();
+        assert_eq!(
+            scalar.to_string(),
+            "`/servers/1/port` is a number, not an object or array"
+        );
+        let no_key = resolve(pointer("/servers/0").key()).unwrap_err();
+        assert!(matches!(no_key, PointerError::NoKey { .. }));
+        let root = resolve(pointer("/nope")).unwrap_err();
+        assert_eq!(root.to_string(), "the document has no `nope`");
+    }
+
+    #[test]
+    fn rejects_invalid_pointers() {
+        assert_eq!(
+            JsonPointer::parse("servers"),
+            Err(PointerError::Syntax { pos: 0 })
+        );
+        assert_eq!(
+            JsonPointer::parse("/a~2"),
+            Err(PointerError::Syntax { pos: 2 })
+        );
+    }
+
+    #[test]
+    fn cooks_through_a_source() {
+        let source = Source::new("config.json", JsonFormat, INPUT.as_bytes());
+        assert_eq!(source.slice(&pointer("/servers/0/port")), Ok(&b"80"[..]));
+        let error = source.render(&pointer("/servers/2")).unwrap_err();
+        assert!(matches!(error, JsonCookError::NoSuchPath(_)));
+        assert_eq!(error.to_string(), "`/servers` has no `2`");
+    }
+}
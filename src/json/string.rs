@@ -0,0 +1,408 @@
+//! Decoding JSON string tokens, and mapping decoded text back to the source.
+
+use core::fmt;
+
+use super::token::{self, char_len};
+use super::{JsonCookError, JsonError, Token};
+use crate::coord::RawCoordinate;
+use crate::format::{cooked_pos, CookError, JsonFormat, ToCooked};
+use crate::pos::{Pos, Position};
+use crate::span::{Cooked, Span};
+use crate::validate::{check_bounds, SpanError};
+
+/// The coordinate system of positions in the decoded text of a JSON string: byte offsets
+/// after escapes like `\n` and `\u00e9` have been replaced by the chars they stand for.
+///
+/// See [`DecodedString`] and [`StringSpan`] for converting them to source bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Decoded {}
+
+impl RawCoordinate for Decoded {
+    const NAME: &'static str = "Decoded";
+}
+
+/// A JSON string's decoded text, with a map from it back to the bytes of the source.
+///
+/// Positions in the decoded text are in the [`Decoded`] coordinate system. Cooking a
+/// `Span<Decoded>` gives the source bytes it was decoded from: a span that covers any part of
+/// an escape such as `\n` or `\u00e9` covers all of it in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedString {
+    text: String,
+    /// Where the string token starts in the source
+    start: usize,
+    /// The decoded text, in runs
+    runs: Vec<Run>,
+    /// Where the closing quote is, relative to `start`
+    end: usize,
+}
+
+/// A run of decoded text: either copied byte for byte from the source, or the one char an
+/// escape (or bytes that aren't UTF-8) decoded to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Run {
+    /// Where the run starts in the decoded text
+    decoded: usize,
+    /// Where it starts in the string token
+    source: usize,
+    copied: bool,
+}
+
+impl DecodedString {
+    /// Decode the string token at `string` (quotes included) in `input`, recording where each
+    /// part of the decoded text came from. Invalid escapes decode as U+FFFD.
+    pub fn decode<P: Position>(
+        input: &[u8],
+        string: Span<Cooked, P>,
+    ) -> Result<Self, JsonCookError> {
+        let (start, end) = check_bounds(&string, input.len())?;
+        let raw = &input[start..end];
+        let not_a_string = JsonCookError::NotAString {
+            start: start as u64,
+        };
+        // Lexed directly, as the token can only start here
+        if raw.first() != Some(&b'"') || token::string(raw) != (Token::String, raw.len()) {
+            return Err(not_a_string);
+        }
+        let (text, runs) = unescape(raw, |_, _, _| {});
+        Ok(Self {
+            text,
+            start,
+            runs,
+            end: raw.len() - 1,
+        })
+    }
+
+    /// The decoded text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Convert a span in the decoded text into the source bytes it was decoded from, or
+    /// report a span that runs past the end of the text or splits a char copied from the
+    /// source. Splitting the char an escape decoded to is fine: the span covers the escape.
+    pub fn try_cook<P: Position>(
+        &self,
+        span: Span<Decoded, P>,
+    ) -> Result<Span<Cooked, P>, CookError> {
+        let (start, end) = check_bounds(&span, self.text.len())?;
+        if let Some(pos) = [start, end]
+            .into_iter()
+            .find(|&pos| self.splits_copied(pos))
+        {
+            return Err(SpanError::NotCharBoundary { pos: pos as u64 }.into());
+        }
+        let source_start = self.source_pos(start, false);
+        let source_end = match start == end {
+            true => source_start,
+            false => self.source_pos(end, true),
+        };
+        Ok(Span::new(
+            cooked_pos(self.start + source_start)?,
+            cooked_pos(source_end - source_start)?,
+        ))
+    }
+
+    /// [`DecodedString::try_cook`], panicking if the span doesn't fit
+    ///
+    /// # Panics
+    ///
+    /// If [`DecodedString::try_cook`] fails.
+    pub fn cook<P: Position>(&self, span: Span<Decoded, P>) -> Span<Cooked, P> {
+        self.try_cook(span)
+            .unwrap_or_else(|e| panic!("failed to cook span: {e}"))
+    }
+
+    /// Whether decoded position `pos` is inside a char copied from the source, which has no
+    /// position in the source to cook to
+    fn splits_copied(&self, pos: usize) -> bool {
+        !self.text.is_char_boundary(pos) && self.run_at(pos).copied
+    }
+
+    /// The run containing decoded position `pos`: the last one starting at or before it
+    fn run_at(&self, pos: usize) -> Run {
+        self.runs[self.runs.partition_point(|run| run.decoded <= pos) - 1]
+    }
+
+    /// Where decoded position `pos` is in the string token. A position inside the char an
+    /// escape decoded to is moved to the start of the escape, or its end if `round_up`.
+    fn source_pos(&self, pos: usize, round_up: bool) -> usize {
+        if pos == self.text.len() {
+            return self.end;
+        }
+        let run = self.run_at(pos);
+        if run.copied || pos == run.decoded || !round_up {
+            return run.source + if run.copied { pos - run.decoded } else { 0 };
+        }
+        let next = self.runs.iter().find(|next| next.decoded > pos);
+        next.map_or(self.end, |next| next.source)
+    }
+}
+
+/// A span inside the decoded text of the JSON string at `string`.
+///
+/// This is to a JSON string what [`ArgSpan`](crate::ArgSpan) is to a CLI arg: a validator
+/// working on decoded values reports a `StringSpan`, and it cooks with
+/// [`ToCooked`] against [`JsonFormat`] into the exact source bytes, escapes and all.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StringSpan<P = Pos> {
+    /// The string token, quotes included
+    pub string: Span<Cooked, P>,
+    /// The span within its decoded text
+    pub decoded: Span<Decoded, P>,
+}
+
+impl<P: Position> StringSpan<P> {
+    /// Creates a span of `decoded` within the string token at `string`
+    pub fn new(string: Span<Cooked, P>, decoded: Span<Decoded, P>) -> Self {
+        Self { string, decoded }
+    }
+}
+
+impl<'input, P: Position> ToCooked<'input, JsonFormat, P> for StringSpan<P> {
+    type Error = JsonCookError;
+
+    fn try_to_cooked(
+        self,
+        _format: &JsonFormat,
+        input: &[u8],
+    ) -> Result<Span<Cooked, P>, JsonCookError> {
+        let decoded = DecodedString::decode(input, self.string)?;
+        Ok(decoded.try_cook(self.decoded)?)
+    }
+}
+
+/// Shown as `string:decoded`, e.g. `4..16:1+2`
+impl<P: Position> fmt::Display for StringSpan<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.string, self.decoded)
+    }
+}
+
+impl<P: Position> fmt::Debug for StringSpan<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "String[{self}]")
+    }
+}
+
+/// Decode the string token `raw` (quotes included), calling `report` with the byte range
+/// within `raw` of each problem found. Problems decode as U+FFFD, or as written for control
+/// characters, so the rest of the string is still usable.
+///
+/// Also returns the runs the decoded text is made of, for [`DecodedString`].
+pub(crate) fn unescape(
+    raw: &[u8],
+    mut report: impl FnMut(usize, usize, JsonError),
+) -> (String, Vec<Run>) {
+    let end = raw.len() - 1; // the closing quote
+    let mut out = String::with_capacity(end);
+    let mut runs: Vec<Run> = Vec::new();
+    let mut i = 1;
+    while i < end {
+        match raw[i] {
+            b'\\' => {
+                let (decoded, len) = escape(&raw[i..end]);
+                if decoded.is_none() {
+                    report(i, i + len, JsonError::InvalidEscape);
+                }
+                replace(
+                    &mut out,
+                    &mut runs,
+                    i,
+                    decoded.unwrap_or(char::REPLACEMENT_CHARACTER),
+                );
+                i += len;
+            }
+            b if b < 0x20 => {
+                report(i, i + 1, JsonError::ControlCharacter);
+                copy(
+                    &mut out,
+                    &mut runs,
+                    i,
+                    char::from(b).encode_utf8(&mut [0; 4]),
+                );
+                i += 1;
+            }
+            _ => {
+                // Everything up to the next escape or control character
+                let len = raw[i..end]
+                    .iter()
+                    .position(|&b| b == b'\\' || b < 0x20)
+                    .unwrap_or(end - i);
+                for chunk in raw[i..i + len].utf8_chunks() {
+                    copy(&mut out, &mut runs, i, chunk.valid());
+                    i += chunk.valid().len();
+                    if !chunk.invalid().is_empty() {
+                        report(i, i + chunk.invalid().len(), JsonError::InvalidUtf8);
+                        replace(&mut out, &mut runs, i, char::REPLACEMENT_CHARACTER);
+                        i += chunk.invalid().len();
+                    }
+                }
+            }
+        }
+    }
+    (out, runs)
+}
+
+/// Append `text`, copied from `source` in the string token
+fn copy(out: &mut String, runs: &mut Vec<Run>, source: usize, text: &str) {
+    if !runs.last().is_some_and(|run| run.copied) {
+        runs.push(Run {
+            decoded: out.len(),
+            source,
+            copied: true,
+        });
+    }
+    out.push_str(text);
+}
+
+/// Append `c`, decoded from the escape (or invalid bytes) at `source` in the string token
+fn replace(out: &mut String, runs: &mut Vec<Run>, source: usize, c: char) {
+    runs.push(Run {
+        decoded: out.len(),
+        source,
+        copied: false,
+    });
+    out.push(c);
+}
+
+/// The char an escape at the start of `raw` decodes to, if it is valid, and its length
+fn escape(raw: &[u8]) -> (Option<char>, usize) {
+    let simple = match raw.get(1) {
+        None => return (None, 1),
+        Some(b'u') => return unicode_escape(raw),
+        Some(b'"') => '"',
+        Some(b'\\') => '\\',
+        Some(b'/') => '/',
+        Some(b'b') => '\u{8}',
+        Some(b'f') => '\u{c}',
+        Some(b'n') => '\n',
+        Some(b'r') => '\r',
+        Some(b't') => '\t',
+        // Don't split the escaped character, if it is more than a byte
+        Some(_) => return (None, 1 + char_len(&raw[1..])),
+    };
+    (Some(simple), 2)
+}
+
+/// A `\uXXXX` escape at the start of `raw`, or a surrogate pair of them
+fn unicode_escape(raw: &[u8]) -> (Option<char>, usize) {
+    let Some(high) = hex4(&raw[2..]) else {
+        let digits = raw[2..]
+            .iter()
+            .take(4)
+            .take_while(|b| b.is_ascii_hexdigit());
+        return (None, 2 + digits.count());
+    };
+    if let Some(c) = char::from_u32(high) {
+        return (Some(c), 6);
+    }
+    // A high surrogate must be followed by an escaped low surrogate
+    let low = raw[6..].strip_prefix(b"\\u").and_then(hex4);
+    match low {
+        Some(low @ 0xDC00..=0xDFFF) if (0xD800..0xDC00).contains(&high) => {
+            let c = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+            (char::from_u32(c), 12)
+        }
+        _ => (None, 6),
+    }
+}
+
+/// The value of the 4 hex digits at the start of `raw`
+fn hex4(raw: &[u8]) -> Option<u32> {
+    let digits = core::str::from_utf8(raw.get(..4)?).ok()?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decoded as `a`, a line break, `é` twice (once escaped), the escaped surrogate pair for
+    /// `😀`, U+FFFD for the invalid `\q`, and `z`
+    const INPUT: &str = r#"{"k": "a\né\u00e9\ud83d\ude00\qz"}"#;
+
+    fn decoded() -> DecodedString {
+        let string = Span::<Cooked>::new(6, INPUT.len() as u32 - 7);
+        DecodedString::decode(INPUT.as_bytes(), string).unwrap()
+    }
+
+    /// The source text `start+len` in the decoded text cooks to
+    fn source(start: u32, len: u32) -> &'static str {
+        &INPUT[decoded().cook(Span::<Decoded>::new(start, len))]
+    }
+
+    #[test]
+    fn decodes() {
+        assert_eq!(decoded().text(), "a\n\u{e9}\u{e9}\u{1f600}\u{fffd}z");
+    }
+
+    #[test]
+    fn maps_escapes_to_their_source() {
+        assert_eq!(source(0, 1), "a");
+        assert_eq!(source(1, 1), r"\n");
+        assert_eq!(source(2, 2), "\u{e9}");
+        assert_eq!(source(4, 2), r"\u00e9");
+        assert_eq!(source(6, 4), r"\ud83d\ude00");
+        assert_eq!(source(10, 3), r"\q");
+        assert_eq!(source(13, 1), "z");
+        assert_eq!(source(0, 14), &INPUT[7..INPUT.len() - 2]);
+    }
+
+    #[test]
+    fn partial_escapes_cover_the_whole_escape() {
+        // Half of the escaped `é`, and the middle of the surrogate pair's char
+        assert_eq!(source(5, 1), r"\u00e9");
+        assert_eq!(source(7, 2), r"\ud83d\ude00");
+        // From inside one escape to inside the next
+        assert_eq!(source(5, 3), r"\u00e9\ud83d\ude00");
+    }
+
+    #[test]
+    fn points() {
+        assert_eq!(decoded().cook(Span::<Decoded>::point(0)), Span::point(7));
+        assert_eq!(decoded().cook(Span::<Decoded>::point(2)), Span::point(10));
+        let end = INPUT.len() as u32 - 2;
+        assert_eq!(decoded().cook(Span::<Decoded>::point(14)), Span::point(end));
+    }
+
+    #[test]
+    fn errors() {
+        let past_end = decoded().try_cook(Span::<Decoded>::new(10, 5));
+        assert!(matches!(
+            past_end,
+            Err(CookError::Span(SpanError::EndOutOfRange { .. }))
+        ));
+        // Inside the `é` copied as is, unlike the escaped one
+        let split = decoded().try_cook(Span::<Decoded>::new(3, 1));
+        assert_eq!(
+            split,
+            Err(CookError::Span(SpanError::NotCharBoundary { pos: 3 }))
+        );
+        let not_a_string = DecodedString::decode(INPUT.as_bytes(), Span::<Cooked>::new(0, 3));
+        assert_eq!(not_a_string, Err(JsonCookError::NotAString { start: 0 }));
+        let wide = DecodedString::decode(br#""\"""#, Span::<Cooked, u64>::new(0, 4));
+        assert_eq!(wide.unwrap().text(), "\"");
+    }
+
+    #[test]
+    fn string_span_cooks_against_json() {
+        let string = Span::<Cooked>::new(6, INPUT.len() as u32 - 7);
+        let span = StringSpan::new(string, Span::new(6, 4));
+        let cooked = span.to_cooked(&JsonFormat, INPUT.as_bytes());
+        assert_eq!(&INPUT[cooked], r"\ud83d\ude00");
+        let not_a_string = StringSpan::new(Span::<Cooked>::new(0, 3), Span::point(0));
+        assert_eq!(
+            not_a_string.try_to_cooked(&JsonFormat, INPUT.as_bytes()),
+            Err(JsonCookError::NotAString { start: 0 })
+        );
+        let past_end = StringSpan::new(string, Span::new(10, 5));
+        assert!(matches!(
+            past_end.try_to_cooked(&JsonFormat, INPUT.as_bytes()),
+            Err(JsonCookError::Cook(CookError::Span(_)))
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+//! Splitting a JSON document into spanned tokens.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::pos::{Pos, PosOverflow, Position};
+use crate::span::{Cooked, Span, Spannable, Spanned};
+
+/// A JSON token. Its text is whatever its span covers in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Token {
+    /// `{`
+    BeginObject,
+    /// `}`
+    EndObject,
+    /// `[`
+    BeginArray,
+    /// `]`
+    EndArray,
+    /// `:`
+    Colon,
+    /// `,`
+    Comma,
+    /// A string, including its quotes. Escapes are left as written
+    String,
+    /// A number
+    Number,
+    /// `true`
+    True,
+    /// `false`
+    False,
+    /// `null`
+    Null,
+    /// A run of spaces, tabs and line breaks
+    Whitespace,
+    /// Input that isn't a valid token
+    Error(LexError),
+}
+
+impl Token {
+    /// Whether this is whitespace, which carries no meaning in JSON
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Whitespace)
+    }
+}
+
+/// Shown as it would be in an "expected ..., found ..." message
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Token::BeginObject => "`{`",
+            Token::EndObject => "`}`",
+            Token::BeginArray => "`[`",
+            Token::EndArray => "`]`",
+            Token::Colon => "`:`",
+            Token::Comma => "`,`",
+            Token::String => "a string",
+            Token::Number => "a number",
+            Token::True => "`true`",
+            Token::False => "`false`",
+            Token::Null => "`null`",
+            Token::Whitespace => "whitespace",
+            Token::Error(_) => "an invalid token",
+        })
+    }
+}
+
+/// Why some input isn't a valid token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LexError {
+    /// A character that can't start a token, such as `'` or `/`
+    UnexpectedChar,
+    /// A string with no closing quote before the end of the line
+    UnterminatedString,
+    /// Something number-like that isn't a JSON number, such as `01`, `1.` or `+1`
+    InvalidNumber,
+    /// A word other than `true`, `false` and `null`, such as `True` or an unquoted key
+    InvalidLiteral,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LexError::UnexpectedChar => "unexpected character",
+            LexError::UnterminatedString => "unterminated string",
+            LexError::InvalidNumber => "invalid number",
+            LexError::InvalidLiteral => "invalid literal, expected `true`, `false` or `null`",
+        })
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// The tokens of `input`, whitespace included, with byte-accurate cooked spans of width `P`.
+///
+/// The tokens cover the input exactly: each starts where the last one ended, and invalid
+/// input comes out as [`Token::Error`] rather than ending the stream.
+///
+/// Fails up front if `input` is longer than `P` can address, so the stream never stops
+/// partway; `tokenize::<u64>` reads inputs over 4 GiB.
+pub fn tokenize<P: Position>(input: &[u8]) -> Result<Tokens<'_, P>, PosOverflow> {
+    P::from_usize(input.len()).ok_or(PosOverflow)?;
+    Ok(Tokens {
+        input,
+        pos: 0,
+        _p: PhantomData,
+    })
+}
+
+/// Iterator over the tokens of a JSON document; see [`tokenize`]
+#[derive(Debug, Clone)]
+pub struct Tokens<'a, P = Pos> {
+    input: &'a [u8],
+    pos: usize,
+    _p: PhantomData<P>,
+}
+
+impl<P: Position> Iterator for Tokens<'_, P> {
+    type Item = Spanned<Token, Cooked, P>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.input[self.pos..];
+        let first = *rest.first()?;
+        let (token, len) = match first {
+            b'{' => (Token::BeginObject, 1),
+            b'}' => (Token::EndObject, 1),
+            b'[' => (Token::BeginArray, 1),
+            b']' => (Token::EndArray, 1),
+            b':' => (Token::Colon, 1),
+            b',' => (Token::Comma, 1),
+            b' ' | b'\t' | b'\n' | b'\r' => (Token::Whitespace, run(rest, is_whitespace)),
+            b'"' => string(rest),
+            // `+` can't start a JSON number, but `+1` is one bad number rather than two tokens
+            b'-' | b'+' | b'0'..=b'9' => {
+                let len = run(rest, is_number_char);
+                if is_number(&rest[..len]) {
+                    (Token::Number, len)
+                } else {
+                    (Token::Error(LexError::InvalidNumber), len)
+                }
+            }
+            _ if first.is_ascii_alphabetic() => {
+                let len = run(rest, |b| b.is_ascii_alphanumeric() || b == b'_');
+                let token = match &rest[..len] {
+                    b"true" => Token::True,
+                    b"false" => Token::False,
+                    b"null" => Token::Null,
+                    _ => Token::Error(LexError::InvalidLiteral),
+                };
+                (token, len)
+            }
+            _ => (Token::Error(LexError::UnexpectedChar), char_len(rest)),
+        };
+        let start = self.pos;
+        self.pos += len;
+        // `tokenize` checked that every position in the input fits
+        let pos = |n: usize| P::from_usize(n).unwrap_or(P::MAX);
+        Some(token.with_span(Span::new(pos(start), pos(len))))
+    }
+}
+
+/// The length of the run of bytes at the start of `input` matching `f`
+fn run(input: &[u8], f: impl Fn(u8) -> bool) -> usize {
+    input.iter().position(|&b| !f(b)).unwrap_or(input.len())
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+fn is_number_char(b: u8) -> bool {
+    matches!(b, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')
+}
+
+/// Whether `text` matches `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`
+fn is_number(text: &[u8]) -> bool {
+    let text = text.strip_prefix(b"-").unwrap_or(text);
+    let int = run(text, |b| b.is_ascii_digit());
+    if int == 0 || int > 1 && text[0] == b'0' {
+        return false;
+    }
+    let mut rest = &text[int..];
+    if let Some(frac) = rest.strip_prefix(b".") {
+        let digits = run(frac, |b| b.is_ascii_digit());
+        if digits == 0 {
+            return false;
+        }
+        rest = &frac[digits..];
+    }
+    if let Some(exp) = rest.strip_prefix(b"e").or_else(|| rest.strip_prefix(b"E")) {
+        let exp = exp
+            .strip_prefix(b"+")
+            .or_else(|| exp.strip_prefix(b"-"))
+            .unwrap_or(exp);
+        let digits = run(exp, |b| b.is_ascii_digit());
+        if digits == 0 {
+            return false;
+        }
+        rest = &exp[digits..];
+    }
+    rest.is_empty()
+}
+
+/// A string starting at the `"` at the start of `input`.
+///
+/// A string left open runs to the end of its line, so the next line still tokenizes.
+pub(super) fn string(input: &[u8]) -> (Token, usize) {
+    let mut i = 1;
+    while let Some(&b) = input.get(i) {
+        match b {
+            b'"' => return (Token::String, i + 1),
+            b'\n' | b'\r' => break,
+            // An escaped quote doesn't close the string, but an escaped line break does
+            b'\\' => match input.get(i + 1) {
+                Some(b'\n' | b'\r') | None => i += 1,
+                Some(_) => i += 2,
+            },
+            _ => i += 1,
+        }
+    }
+    (Token::Error(LexError::UnterminatedString), i)
+}
+
+/// The length of the UTF-8 character at the start of `input`, or 1 if it isn't one
+pub(super) fn char_len(input: &[u8]) -> usize {
+    let len = match input[0] {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    };
+    match core::str::from_utf8(&input[..len.min(input.len())]) {
+        Ok(_) => len,
+        Err(_) => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<(Token, &str)> {
+        tokenize::<u32>(input.as_bytes())
+            .unwrap()
+            .map(|t| (t.node, &input[t.span]))
+            .collect()
+    }
+
+    #[test]
+    fn covers_the_input() {
+        let input = "{\"a\\\"\": [1.5e3, -0, true, null]}\n";
+        let mut end = 0;
+        for token in tokenize::<u32>(input.as_bytes()).unwrap() {
+            assert_eq!(token.span.start, end);
+            end = token.span.end();
+        }
+        assert_eq!(end as usize, input.len());
+    }
+
+    #[test]
+    fn invalid_numbers_are_one_token() {
+        use Token::Error;
+        let invalid = Error(LexError::InvalidNumber);
+        for number in ["+1", "01", "1.", "-", "1e"] {
+            assert_eq!(tokens(number), [(invalid, number)]);
+        }
+        assert_eq!(tokens("-0.5E+2"), [(Token::Number, "-0.5E+2")]);
+    }
+
+    #[test]
+    fn unterminated_string_ends_at_the_line() {
+        let unterminated = Token::Error(LexError::UnterminatedString);
+        assert_eq!(
+            tokens("\"abc\n1"),
+            [
+                (unterminated, "\"abc"),
+                (Token::Whitespace, "\n"),
+                (Token::Number, "1")
+            ]
+        );
+    }
+
+    #[test]
+    fn spans_have_the_width_asked_for() {
+        let wide: Vec<Spanned<Token, Cooked, u64>> = tokenize(b"[1]").unwrap().collect();
+        assert_eq!(wide[1], Token::Number.with_span(Span::new(1, 1)));
+        assert!(tokenize::<u32>(b"[1]").is_ok());
+    }
+}
@@ -0,0 +1,56 @@
+//! Typed spans over two coordinate systems, `Span<Raw<F>>` and `Span<Cooked>`.
+//!
+//! This is the final design from the `v16` demo, packaged as a library:
+//!
+//! - [`span`]: the [`Raw`]/[`Cooked`] markers, [`Span<C>`] and [`Spanned<T, C>`]
+//! - [`cli`]: the [`CliArg`] types CLI formats take, and [`ArgSpan`] for positions inside a
+//!   single arg
+//! - [`context`]: [`CookContext`], for cooking many raw CLI spans against one input
+//! - [`coord`]: the sealed [`Coordinate`] trait naming and describing each marker
+//! - [`pos`]: the [`Position`] widths (`u32` by default, or `u64`) spans are stored in
+//! - [`format`]: the [`Format`] trait, [`ToCooked`] and [`process_span`], plus the
+//!   [`CliFormat`] and [`JsonFormat`] formats used throughout the demos, and v1's
+//!   [`RawFormat`] and [`CookedFormat`]
+//! - [`json`]: tokenizing and parsing JSON documents into spanned tokens and values
+//! - [`render`]: underlining a cooked span against the input it points into, and
+//!   [`RenderInput`] for formats rendering that input themselves
+//! - [`source`]: [`Source`], an input bundled with its format, rendered text and name
+//! - [`parse`]: parsing spans from `start..end`, `start+len` or `line:col-line:col` text
+//! - [`slice`]: getting the text (or CLI args) a span covers
+//! - [`uncook`]: [`ToRaw`], converting cooked spans back into a format's raw coordinates
+//! - [`validate`]: checking a span against the input it claims to describe
+//!
+//! A format associates a coordinate system (`SpanType`) and an input type with itself.
+//! Spans in a format's `Raw<F>` coordinate system are cooked into byte offsets by a
+//! [`ToCooked`] impl that sees the input, and spans that are already `Cooked` pass through
+//! unchanged.
+
+pub mod cli;
+pub mod context;
+pub mod coord;
+pub mod format;
+pub mod json;
+pub mod parse;
+pub mod pos;
+pub mod render;
+pub mod slice;
+pub mod source;
+pub mod span;
+pub mod uncook;
+pub mod validate;
+
+pub use cli::{render_args, ArgSpan, ArgsFormat, CliArg};
+pub use context::CookContext;
+pub use coord::{Coordinate, RawCoordinate};
+pub use format::{
+    process_span, try_process_span, CliFormat, CookError, CookRaw, CookSpan, CookedFormat,
+    CowCliFormat, Format, JsonFormat, OsCliFormat, OwnedCliFormat, RawFormat, ToCooked,
+};
+pub use parse::{LineCol, LineColSpan, ParseSpanError};
+pub use pos::{Pos, PosOverflow, Position};
+pub use render::{render_span, visualize_span, RenderInput, EOF_MARKER};
+pub use slice::SliceInput;
+pub use source::Source;
+pub use span::{Cooked, Raw, Span, Spannable, Spanned};
+pub use uncook::{FromCooked, RawError, ToRaw};
+pub use validate::{SpanError, Validate};
@@ -0,0 +1,300 @@
+//! Parsing spans from text, for CLI flags and test fixtures.
+//!
+//! A span is written as `start..end` or `start+len`, which is how [`Span`]'s `Display`
+//! writes it, so spans round-trip. A cooked span can also be written as
+//! `line:col-line:col` ([`LineColSpan`]), which needs the input text to resolve.
+
+use core::fmt;
+use core::num::ParseIntError;
+use core::str::FromStr;
+
+use crate::pos::Position;
+use crate::span::{Cooked, Span};
+use crate::validate::{SpanError, Validate};
+
+/// Why a span could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseSpanError {
+    /// The text is not in any of the accepted forms
+    Syntax,
+    /// A `line:col-line:col` span was parsed without the input it needs to resolve; see
+    /// [`Span::parse_in`]
+    NeedsInput,
+    /// A position is not a valid number for the span's position width
+    Number(ParseIntError),
+    /// The numbers parsed but don't make a valid span
+    Span(SpanError),
+    /// A `line:col` position doesn't exist in the input
+    LineColOutOfRange(LineCol),
+}
+
+impl fmt::Display for ParseSpanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSpanError::Syntax => f.write_str(
+                "expected a span as `start..end` or `start+len`, or against the input text, \
+                 `line:col-line:col`",
+            ),
+            ParseSpanError::NeedsInput => f.write_str(
+                "a `line:col-line:col` span can only be parsed against the input text, with \
+                 `Span::parse_in`",
+            ),
+            ParseSpanError::Number(e) => write!(f, "invalid span position: {e}"),
+            ParseSpanError::Span(e) => e.fmt(f),
+            ParseSpanError::LineColOutOfRange(pos) => {
+                write!(f, "position {pos} is not in the input")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseSpanError {}
+
+impl From<ParseIntError> for ParseSpanError {
+    fn from(e: ParseIntError) -> Self {
+        ParseSpanError::Number(e)
+    }
+}
+
+impl From<SpanError> for ParseSpanError {
+    fn from(e: SpanError) -> Self {
+        ParseSpanError::Span(e)
+    }
+}
+
+impl<C, P: Position + FromStr<Err = ParseIntError>> FromStr for Span<C, P> {
+    type Err = ParseSpanError;
+
+    /// Parse `start..end` or `start+len`. The `line:col-line:col` form needs the input, so
+    /// is an error here; see [`Span::parse_in`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(':') {
+            Err(ParseSpanError::NeedsInput)
+        } else if let Some((start, end)) = s.split_once("..") {
+            Ok(Span::from_bounds(
+                start.trim().parse()?,
+                end.trim().parse()?,
+            )?)
+        } else if let Some((start, len)) = s.split_once('+') {
+            Ok(Span::try_new(start.trim().parse()?, len.trim().parse()?)?)
+        } else {
+            Err(ParseSpanError::Syntax)
+        }
+    }
+}
+
+/// A 1-based line and column, with the column counted in chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column number in chars, starting at 1
+    pub col: usize,
+}
+
+impl LineCol {
+    /// The line and column of byte offset `pos` in `input`, if it is on a char boundary
+    pub fn of_offset(input: &str, pos: usize) -> Option<Self> {
+        let before = input.get(..pos)?;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Some(LineCol {
+            line: before.matches('\n').count() + 1,
+            col: before[line_start..].chars().count() + 1,
+        })
+    }
+
+    /// The byte offset of this position in `input`.
+    ///
+    /// A column one past the last char of a line is its end (before the newline).
+    pub fn offset_in(&self, input: &str) -> Option<usize> {
+        let line_start = match self.line {
+            0 => return None,
+            1 => 0,
+            n => input.match_indices('\n').nth(n - 2)?.0 + 1,
+        };
+        let line = input[line_start..].split('\n').next()?;
+        let col = self.col.checked_sub(1)?;
+        let in_line = line
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([line.len()])
+            .nth(col)?;
+        Some(line_start + in_line)
+    }
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+impl FromStr for LineCol {
+    type Err = ParseSpanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (line, col) = s.split_once(':').ok_or(ParseSpanError::Syntax)?;
+        Ok(LineCol {
+            line: line.trim().parse()?,
+            col: col.trim().parse()?,
+        })
+    }
+}
+
+/// A span written as `line:col-line:col`, with an exclusive end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LineColSpan {
+    /// Position of the first char in the span
+    pub start: LineCol,
+    /// Position just past the last char in the span
+    pub end: LineCol,
+}
+
+impl LineColSpan {
+    /// Resolve to a cooked span over `input`
+    pub fn resolve<P: Position>(&self, input: &str) -> Result<Span<Cooked, P>, ParseSpanError> {
+        let offset = |pos: LineCol| {
+            let offset = pos
+                .offset_in(input)
+                .ok_or(ParseSpanError::LineColOutOfRange(pos))?;
+            P::from_usize(offset).ok_or(ParseSpanError::Span(SpanError::Overflow))
+        };
+        Ok(Span::from_bounds(offset(self.start)?, offset(self.end)?)?)
+    }
+}
+
+impl fmt::Display for LineColSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl FromStr for LineColSpan {
+    type Err = ParseSpanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once('-').ok_or(ParseSpanError::Syntax)?;
+        Ok(LineColSpan {
+            start: start.parse()?,
+            end: end.parse()?,
+        })
+    }
+}
+
+impl<P: Position + FromStr<Err = ParseIntError>> Span<Cooked, P> {
+    /// Parse a span in any of the `start..end`, `start+len` or `line:col-line:col` forms,
+    /// checking that it lies within `input`
+    pub fn parse_in(s: &str, input: &str) -> Result<Self, ParseSpanError> {
+        let span = if s.contains(':') {
+            s.parse::<LineColSpan>()?.resolve(input)?
+        } else {
+            s.parse::<Self>()?
+        };
+        span.validate(input)?;
+        Ok(span)
+    }
+
+    /// The `line:col-line:col` form of this span in `input`, if it lies on char boundaries
+    pub fn line_col(&self, input: &str) -> Option<LineColSpan> {
+        let start = LineCol::of_offset(input, self.start.to_usize()?)?;
+        let end = LineCol::of_offset(input, self.checked_end()?.to_usize()?)?;
+        Some(LineColSpan { start, end })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::CliFormat;
+    use crate::span::Raw;
+
+    const INPUT: &str = "{\n  \"caf\u{e9}\": 1\n}";
+
+    #[test]
+    fn display_round_trips() {
+        let cooked = Span::<Cooked>::new(4, 6);
+        assert_eq!(cooked.to_string(), "4..10");
+        assert_eq!(format!("{cooked:?}"), "Cooked[4..10]");
+        assert_eq!(cooked.to_string().parse(), Ok(cooked));
+        let raw = Span::<Raw<CliFormat>>::new(1, 2);
+        assert_eq!(raw.to_string(), "1+2");
+        assert_eq!(format!("{raw:?}"), "Raw[1+2]");
+        assert_eq!(raw.to_string().parse(), Ok(raw));
+        // Either form parses into either coordinate system
+        assert_eq!(" 4 + 6 ".parse(), Ok(cooked));
+        assert_eq!("1..3".parse(), Ok(raw));
+    }
+
+    #[test]
+    fn rejects_bad_spans() {
+        let parse = |s: &str| s.parse::<Span>();
+        assert_eq!(parse("4-10"), Err(ParseSpanError::Syntax));
+        assert!(matches!(parse("4..x"), Err(ParseSpanError::Number(_))));
+        assert!(matches!(parse("-1+2"), Err(ParseSpanError::Number(_))));
+        assert_eq!(
+            parse("10..4"),
+            Err(ParseSpanError::Span(SpanError::Inverted {
+                start: 10,
+                end: 4
+            }))
+        );
+        assert_eq!(
+            parse("4294967295+1"),
+            Err(ParseSpanError::Span(SpanError::Overflow))
+        );
+        let needs_input = parse("1:1-1:3").unwrap_err();
+        assert_eq!(needs_input, ParseSpanError::NeedsInput);
+        assert!(needs_input.to_string().contains("Span::parse_in"));
+    }
+
+    #[test]
+    fn resolves_lines_and_columns() {
+        let key: LineColSpan = "2:3-2:9".parse().unwrap();
+        assert_eq!(key.to_string(), "2:3-2:9");
+        // Columns count chars, so the accented char is one column but two bytes
+        let span: Span = key.resolve(INPUT).unwrap();
+        assert_eq!(&INPUT[span], "\"caf\u{e9}\"");
+        assert_eq!(span.line_col(INPUT), Some(key));
+        // One past the last char of a line is its end
+        let line_end: Span = "2:12-3:2"
+            .parse::<LineColSpan>()
+            .unwrap()
+            .resolve(INPUT)
+            .unwrap();
+        assert_eq!(&INPUT[line_end], "\n}");
+        let past = "2:13-2:13"
+            .parse::<LineColSpan>()
+            .unwrap()
+            .resolve::<u32>(INPUT);
+        assert_eq!(
+            past,
+            Err(ParseSpanError::LineColOutOfRange(LineCol {
+                line: 2,
+                col: 13
+            }))
+        );
+    }
+
+    #[test]
+    fn parses_any_form_in_an_input() {
+        assert_eq!(Span::parse_in("2..4", INPUT), Ok(Span::<Cooked>::new(2, 2)));
+        assert_eq!(Span::parse_in("2+2", INPUT), Ok(Span::<Cooked>::new(2, 2)));
+        assert_eq!(
+            Span::parse_in("1:1-2:1", INPUT),
+            Ok(Span::<Cooked>::new(0, 2))
+        );
+        // Spans are checked against the input, char boundaries included
+        assert_eq!(
+            Span::<Cooked>::parse_in("9..10", INPUT),
+            Err(ParseSpanError::Span(SpanError::NotCharBoundary { pos: 9 }))
+        );
+        assert!(matches!(
+            Span::<Cooked>::parse_in("0..99", INPUT),
+            Err(ParseSpanError::Span(SpanError::EndOutOfRange { .. }))
+        ));
+        assert_eq!(
+            Span::<Cooked>::parse_in("1:1", INPUT),
+            Err(ParseSpanError::Syntax)
+        );
+    }
+}
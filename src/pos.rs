@@ -0,0 +1,176 @@
+//! Position widths: the integer type a [`Span`](crate::Span) stores its start and length in.
+
+use core::fmt::{self, Debug, Display};
+use core::hash::Hash;
+use core::ops::{Add, Sub};
+
+/// Position in the input (byte index, if cooked), at the default compact width.
+///
+/// `u32` keeps a span at 8 bytes, which addresses inputs up to 4 GiB; use
+/// `Span<C, u64>` for anything larger.
+pub type Pos = u32;
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for u32 {}
+    impl Sealed for u64 {}
+    impl Sealed for usize {}
+}
+
+/// An unsigned integer type usable as a span's position width: `u32`, `u64` or `usize`.
+///
+/// The trait is sealed; spans are generic over it so the storage width can be picked
+/// per use, while arithmetic and conversions stay checked.
+pub trait Position:
+    sealed::Sealed
+    + Copy
+    + Ord
+    + Hash
+    + Default
+    + Debug
+    + Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + 'static
+{
+    /// Zero at this width
+    const ZERO: Self;
+    /// One at this width
+    const ONE: Self;
+    /// The largest position representable at this width
+    const MAX: Self;
+
+    /// Convert from `usize`, or `None` if it doesn't fit
+    fn from_usize(n: usize) -> Option<Self>;
+    /// Convert to `usize`, or `None` if it doesn't fit (e.g. a `u64` on a 32-bit target)
+    fn to_usize(self) -> Option<usize>;
+    /// Convert to another width, or `None` if it doesn't fit
+    fn cast<Q: Position>(self) -> Option<Q>;
+    /// Checked addition
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    /// Checked subtraction
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    /// Saturating addition
+    fn saturating_add(self, rhs: Self) -> Self;
+    /// Saturating subtraction
+    fn saturating_sub(self, rhs: Self) -> Self;
+    /// Checked addition of a signed offset
+    fn checked_add_signed(self, delta: i64) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn to_u128(self) -> u128;
+    #[doc(hidden)]
+    fn from_u128(n: u128) -> Option<Self>;
+}
+
+macro_rules! impl_position {
+    ($($t:ty),*) => {$(
+        impl Position for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+            const MAX: Self = <$t>::MAX;
+
+            #[inline]
+            fn from_usize(n: usize) -> Option<Self> {
+                Self::try_from(n).ok()
+            }
+
+            #[inline]
+            fn to_usize(self) -> Option<usize> {
+                usize::try_from(self).ok()
+            }
+
+            #[inline]
+            fn cast<Q: Position>(self) -> Option<Q> {
+                Q::from_u128(self.to_u128())
+            }
+
+            #[inline]
+            fn checked_add(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_add(self, rhs)
+            }
+
+            #[inline]
+            fn checked_sub(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_sub(self, rhs)
+            }
+
+            #[inline]
+            fn saturating_add(self, rhs: Self) -> Self {
+                <$t>::saturating_add(self, rhs)
+            }
+
+            #[inline]
+            fn saturating_sub(self, rhs: Self) -> Self {
+                <$t>::saturating_sub(self, rhs)
+            }
+
+            #[inline]
+            fn checked_add_signed(self, delta: i64) -> Option<Self> {
+                Self::try_from(i128::try_from(self).ok()? + i128::from(delta)).ok()
+            }
+
+            #[inline]
+            fn to_u128(self) -> u128 {
+                self as u128
+            }
+
+            #[inline]
+            fn from_u128(n: u128) -> Option<Self> {
+                Self::try_from(n).ok()
+            }
+        }
+    )*};
+}
+
+impl_position!(u32, u64, usize);
+
+/// A position or length didn't fit in the target position width
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PosOverflow;
+
+impl fmt::Display for PosOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("position does not fit in the span's position width")
+    }
+}
+
+impl std::error::Error for PosOverflow {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::{Cooked, Span};
+
+    #[test]
+    fn converts_between_widths() {
+        assert_eq!(u32::from_usize(7), Some(7));
+        assert_eq!(u32::from_usize(u32::MAX as usize + 1), None);
+        assert_eq!(u64::MAX.cast::<u32>(), None);
+        assert_eq!(u32::MAX.cast::<u64>(), Some(u64::from(u32::MAX)));
+        assert_eq!(5u32.checked_add_signed(-5), Some(0));
+        assert_eq!(5u32.checked_add_signed(-6), None);
+        assert_eq!(u32::MAX.checked_add_signed(1), None);
+    }
+
+    #[test]
+    fn widens_spans() {
+        let span = Span::<Cooked>::new(3, u32::MAX - 3);
+        let wide: Span<Cooked, u64> = span.into();
+        assert_eq!(wide, Span::new(3, u64::from(u32::MAX) - 3));
+        assert_eq!(wide.try_cast::<u32>(), Ok(span));
+    }
+
+    #[test]
+    fn narrowing_checks_the_end() {
+        let past_4_gib = Span::<Cooked, u64>::new(1 << 32, 1);
+        assert_eq!(past_4_gib.try_cast::<u32>(), Err(PosOverflow));
+        // The start and length fit, but the end doesn't
+        let ends_past = Span::<Cooked, u64>::new(u64::from(u32::MAX), 1);
+        assert_eq!(ends_past.try_cast::<u32>(), Err(PosOverflow));
+        assert_eq!(
+            Span::<Cooked, u64>::new(1, 2).try_cast::<usize>(),
+            Ok(Span::new(1, 2))
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Rendering cooked spans against the input they point into.
+
+use std::borrow::Cow;
+
+use crate::format::Format;
+use crate::pos::Position;
+use crate::span::{Cooked, Span};
+
+/// Formats that can render their input as the exact bytes their cooked spans index into.
+///
+/// Rendering the input with the format, rather than separately (e.g. by joining CLI args by
+/// hand), is what keeps the carets [`render_span`] draws lined up with the cooked span.
+/// Formats implementing [`ArgsFormat`](crate::ArgsFormat) get this from
+/// [`render_args`](crate::render_args).
+pub trait RenderInput: Format {
+    /// The text cooked spans of this format index into
+    fn render_input<'a>(&self, input: &'a Self::Input<'_>) -> Cow<'a, [u8]>;
+}
+
+/// Marker drawn under the end of the input for a point span there
+pub const EOF_MARKER: &str = "<EOF>";
+
+/// Render the input with a line of `^` carets underneath the bytes covered by `span`.
+///
+/// The output is two lines, `Input: ...` and `Span:  ...`, as printed by the demos.
+/// A zero-width span is drawn as a single caret, or as [`EOF_MARKER`] when it sits at
+/// the end of the input.
+pub fn render_span<T: AsRef<[u8]>, P: Position>(input: T, span: &Span<Cooked, P>) -> String {
+    // A span that can't be addressed as `usize` can't be inside the input either
+    let bounds = span.start.to_usize().zip(span.end().to_usize());
+    render_marked(
+        input.as_ref(),
+        &[(bounds.unwrap_or((usize::MAX, usize::MAX)), '^')],
+    )
+}
+
+/// [`render_span`] for several `(start, end)` byte ranges, each underlined with its own
+/// marker. Where ranges overlap, the first one's marker is drawn.
+pub(crate) fn render_marked(input: &[u8], ranges: &[((usize, usize), char)]) -> String {
+    let input_str = String::from_utf8_lossy(input);
+
+    // One column per char, with a marker under the chars a range covers. Tabs are repeated
+    // so the columns after them still line up.
+    let mut underline = String::with_capacity(input.len());
+    let mut pos = 0;
+    for chunk in input.utf8_chunks() {
+        let chars = chunk.valid().chars().map(|c| (c, c.len_utf8()));
+        let invalid = (!chunk.invalid().is_empty()).then_some(('\u{FFFD}', chunk.invalid().len()));
+        for (c, len) in chars.chain(invalid) {
+            let covers = |&&((start, end), _): &&((usize, usize), char)| {
+                pos < end && pos + len > start || start == end && pos == start
+            };
+            match ranges.iter().find(covers) {
+                Some(&(_, marker)) => underline.push(marker),
+                None if c == '\t' => underline.push('\t'),
+                None => underline.push(' '),
+            }
+            pos += len;
+        }
+    }
+    if ranges
+        .iter()
+        .any(|&((start, end), _)| start == end && start == input.len())
+    {
+        underline.push_str(EOF_MARKER);
+    }
+
+    format!("Input: {input_str}\nSpan:  {underline}")
+}
+
+/// Print [`render_span`] to stdout.
+pub fn visualize_span<T: AsRef<[u8]>, P: Position>(input: T, span: &Span<Cooked, P>) {
+    println!("{}", render_span(input, span));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{CliFormat, ToCooked};
+    use crate::span::Raw;
+
+    #[test]
+    fn underlines_the_span() {
+        let rendered = render_span("--foo bar", &Span::<Cooked>::new(6, 3));
+        assert_eq!(rendered, "Input: --foo bar\nSpan:        ^^^");
+    }
+
+    #[test]
+    fn draws_a_point_as_one_caret() {
+        let rendered = render_span("--foo bar", &Span::<Cooked>::point(5));
+        assert_eq!(rendered, "Input: --foo bar\nSpan:       ^   ");
+    }
+
+    #[test]
+    fn draws_a_point_at_the_end_as_eof() {
+        let input = "[1, 2";
+        let rendered = render_span(input, &Span::<Cooked>::end_of_input(input));
+        assert_eq!(rendered, "Input: [1, 2\nSpan:       <EOF>");
+    }
+
+    #[test]
+    fn end_of_args_cooks_to_the_end_of_the_line() {
+        let args = ["--foo", "bar"];
+        let end: Span<Raw<CliFormat>> = Span::end_of_args(&args[..]);
+        assert_eq!(end, Span::point(2));
+        let cooked = end.to_cooked(&CliFormat, &args[..]);
+        let line = CliFormat.render_input(&args[..]);
+        assert_eq!(cooked, Span::end_of_input(&*line));
+        assert!(render_span(&*line, &cooked).ends_with("   <EOF>"));
+    }
+
+    #[test]
+    fn lines_up_after_wide_chars_and_tabs() {
+        let rendered = render_span("\u{e9}\tx", &Span::<Cooked>::new(3, 1));
+        assert_eq!(rendered, "Input: \u{e9}\tx\nSpan:   \t^");
+    }
+}
@@ -0,0 +1,132 @@
+//! Getting at the input a span points into.
+
+use core::ops::Index;
+
+use crate::cli::ArgsFormat;
+use crate::pos::Position;
+use crate::span::{Cooked, Raw, Span};
+use crate::validate::{check_bounds, check_str, SpanError};
+
+/// Inputs that a cooked (byte offset) span can slice into: `str` and `[u8]`.
+pub trait SliceInput {
+    /// Slice out the bytes covered by `span`
+    fn slice_span<P: Position>(&self, span: Span<Cooked, P>) -> Result<&Self, SpanError>;
+}
+
+impl SliceInput for [u8] {
+    fn slice_span<P: Position>(&self, span: Span<Cooked, P>) -> Result<&Self, SpanError> {
+        let (start, end) = check_bounds(&span, self.len())?;
+        Ok(&self[start..end])
+    }
+}
+
+impl SliceInput for str {
+    fn slice_span<P: Position>(&self, span: Span<Cooked, P>) -> Result<&Self, SpanError> {
+        let (start, end) = check_str(&span, self)?;
+        Ok(&self[start..end])
+    }
+}
+
+impl<P: Position> Span<Cooked, P> {
+    /// The part of `input` this span covers
+    pub fn slice<'a, S: SliceInput + ?Sized>(&self, input: &'a S) -> Result<&'a S, SpanError> {
+        input.slice_span(*self)
+    }
+
+    /// The text this span covers in `input`
+    pub fn text<'a>(&self, input: &'a str) -> Result<&'a str, SpanError> {
+        self.slice(input)
+    }
+}
+
+impl<F: ArgsFormat, P: Position> Span<Raw<F>, P> {
+    /// The CLI args this span covers in `F`'s input, where `start` is an arg index and `len`
+    /// a count of args
+    pub fn slice<'a, 'input: 'a>(
+        &self,
+        input: &'a F::Input<'input>,
+    ) -> Result<&'a [F::Arg<'input>], SpanError> {
+        let args = F::args(input);
+        let (start, end) = check_bounds(self, args.len())?;
+        Ok(&args[start..end])
+    }
+}
+
+impl<P: Position> Index<Span<Cooked, P>> for str {
+    type Output = str;
+
+    /// Panics if the span is out of bounds or not on char boundaries, like `&s[a..b]`
+    fn index(&self, span: Span<Cooked, P>) -> &str {
+        span.slice(self).unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+impl<P: Position> Index<Span<Cooked, P>> for [u8] {
+    type Output = [u8];
+
+    /// Panics if the span is out of bounds, like `&b[a..b]`
+    fn index(&self, span: Span<Cooked, P>) -> &[u8] {
+        span.slice(self).unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::*;
+    use crate::format::{CliFormat, OsCliFormat};
+    use crate::pos::Pos;
+
+    const TEXT: &str = "caf\u{e9} bar";
+
+    #[test]
+    fn slices_text_and_bytes() {
+        let span = Span::<Cooked>::new(6, 3);
+        assert_eq!(span.text(TEXT), Ok("bar"));
+        assert_eq!(&TEXT[span], "bar");
+        assert_eq!(span.slice(TEXT.as_bytes()), Ok(&b"bar"[..]));
+        assert_eq!(Span::<Cooked>::point(9).text(TEXT), Ok(""));
+    }
+
+    #[test]
+    fn reports_spans_that_dont_fit() {
+        assert_eq!(
+            Span::<Cooked>::new(8, 2).text(TEXT),
+            Err(SpanError::EndOutOfRange {
+                end: 10,
+                input_len: 9
+            })
+        );
+        assert_eq!(
+            Span::<Cooked>::point(10).text(TEXT),
+            Err(SpanError::StartOutOfRange {
+                start: 10,
+                input_len: 9
+            })
+        );
+        // Half of a two-byte char is fine as bytes, but not as text
+        let half = Span::<Cooked>::new(0, 4);
+        assert_eq!(half.text(TEXT), Err(SpanError::NotCharBoundary { pos: 4 }));
+        assert_eq!(half.slice(TEXT.as_bytes()), Ok(&b"caf\xc3"[..]));
+        let overflow = Span::<Cooked>::new(1, Pos::MAX);
+        assert_eq!(overflow.slice(TEXT.as_bytes()), Err(SpanError::Overflow));
+    }
+
+    #[test]
+    #[should_panic = "not on a char boundary"]
+    fn indexing_inside_a_char_panics() {
+        let _ = &TEXT[Span::<Cooked>::new(4, 1)];
+    }
+
+    #[test]
+    fn slices_args() {
+        let args = ["--foo", "bar", "baz"];
+        let span = Span::<Raw<CliFormat>>::new(1, 2);
+        assert_eq!(span.slice(&args[..]), Ok(&["bar", "baz"][..]));
+        assert!(Span::<Raw<CliFormat>>::new(2, 2).slice(&args[..]).is_err());
+        let os_args = [OsString::from("-v")];
+        let span = Span::<Raw<OsCliFormat>>::new(0, 1);
+        assert_eq!(span.slice(&os_args[..]), Ok(&os_args[..]));
+    }
+}
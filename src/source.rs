@@ -0,0 +1,170 @@
+//! [`Source`]: an input bundled with its format, rendered text and name.
+
+use std::borrow::Cow;
+
+use crate::format::{CookError, ToCooked};
+use crate::pos::Position;
+use crate::render::{render_span, RenderInput};
+use crate::span::{Cooked, Span};
+use crate::validate::check_bounds;
+
+/// An input together with the format its spans are in, the text those spans cook into, and
+/// a name to show in diagnostics (a file name, or e.g. `"args"` for the command line).
+///
+/// The text is rendered once by the format itself (see [`RenderInput`]), so a span cooked
+/// by a `Source` always lines up with the text it slices and renders.
+#[derive(Debug)]
+pub struct Source<'a, 'input, F: RenderInput> {
+    name: Cow<'a, str>,
+    format: F,
+    input: &'a F::Input<'input>,
+    text: Cow<'a, [u8]>,
+}
+
+impl<'a, 'input, F: RenderInput> Source<'a, 'input, F> {
+    /// Renders `input` with `format`
+    pub fn new(name: impl Into<Cow<'a, str>>, format: F, input: &'a F::Input<'input>) -> Self {
+        let text = format.render_input(input);
+        Self {
+            name: name.into(),
+            format,
+            input,
+            text,
+        }
+    }
+
+    /// The name shown in diagnostics
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The format spans into this source are in
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+
+    /// The input, as given to [`Source::new`]
+    pub fn input(&self) -> &'a F::Input<'input> {
+        self.input
+    }
+
+    /// The text cooked spans index into
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// Cook `span` (a `Span<F::SpanType>`, an already cooked span, or e.g. an
+    /// [`ArgSpan`](crate::ArgSpan)), checking that the result lies within [`Source::text`]
+    pub fn try_cook<S, P>(&self, span: S) -> Result<Span<Cooked, P>, S::Error>
+    where
+        S: ToCooked<'input, F, P>,
+        P: Position,
+    {
+        let cooked = span.try_to_cooked(&self.format, self.input)?;
+        check_bounds(&cooked, self.text.len()).map_err(CookError::from)?;
+        Ok(cooked)
+    }
+
+    /// [`Source::try_cook`], panicking if the span doesn't fit
+    ///
+    /// # Panics
+    ///
+    /// If [`Source::try_cook`] fails.
+    pub fn cook<S, P>(&self, span: S) -> Span<Cooked, P>
+    where
+        S: ToCooked<'input, F, P>,
+        P: Position,
+    {
+        self.try_cook(span)
+            .unwrap_or_else(|e| panic!("failed to cook span: {e}"))
+    }
+
+    /// The text `span` covers
+    pub fn slice<S, P>(&self, span: S) -> Result<&[u8], S::Error>
+    where
+        S: ToCooked<'input, F, P>,
+        P: Position,
+    {
+        let cooked = self.try_cook(span)?;
+        Ok(&self.text[cooked])
+    }
+
+    /// [`render_span`] for `span` against this source's text, under a `Source:` line naming
+    /// it
+    pub fn render<S, P>(&self, span: S) -> Result<String, S::Error>
+    where
+        S: ToCooked<'input, F, P>,
+        P: Position,
+    {
+        let cooked = self.try_cook(span)?;
+        let rendered = render_span(&*self.text, &cooked);
+        Ok(format!("Source: {}\n{rendered}", self.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{render_args, ArgSpan};
+    use crate::format::{CliFormat, JsonFormat};
+    use crate::span::Raw;
+    use crate::validate::SpanError;
+
+    const ARGS: &[&str] = &["run", "--level=9000", "", "caf\u{e9}"];
+
+    fn args() -> Source<'static, 'static, CliFormat> {
+        Source::new("args", CliFormat, ARGS)
+    }
+
+    #[test]
+    fn renders_the_text_spans_cook_into() {
+        assert_eq!(args().text(), render_args(ARGS).as_bytes());
+        assert_eq!(args().name(), "args");
+        // Every arg cooks to exactly its own text in the rendered line
+        for (i, arg) in ARGS.iter().enumerate() {
+            let span = Span::<Raw<CliFormat>>::new(i as u32, 1);
+            assert_eq!(args().slice(span), Ok(arg.as_bytes()));
+        }
+        let end = Span::<Raw<CliFormat>>::end_of_args(ARGS);
+        assert_eq!(args().cook(end), Span::end_of_input(args().text()));
+    }
+
+    #[test]
+    fn cooks_any_span_for_the_format() {
+        let value: ArgSpan = ArgSpan::value_of(ARGS, 1).unwrap();
+        assert_eq!(args().slice(value), Ok(&b"9000"[..]));
+        assert_eq!(args().cook(Span::<Cooked>::new(0, 3)), Span::new(0, 3));
+        assert_eq!(
+            args().try_cook(Span::<Raw<CliFormat>>::new(1, 1)),
+            Ok(Span::new(4, 12))
+        );
+    }
+
+    #[test]
+    fn checks_spans_against_the_text() {
+        let past_end = args().try_cook(Span::<Cooked>::new(20, 9));
+        assert_eq!(
+            past_end,
+            Err(CookError::Span(SpanError::EndOutOfRange {
+                end: 29,
+                input_len: 23
+            }))
+        );
+        assert!(args().try_cook(Span::<Raw<CliFormat>>::new(4, 1)).is_err());
+    }
+
+    #[test]
+    fn renders_under_its_name() {
+        let rendered = args().render(Span::<Raw<CliFormat>>::new(3, 1)).unwrap();
+        assert_eq!(
+            rendered,
+            "Source: args\nInput: run --level=9000  caf\u{e9}\nSpan:                    ^^^^"
+        );
+        let json = Source::new("config.json", JsonFormat, &b"[1, 2]"[..]);
+        let rendered = json
+            .render(Span::<Cooked>::end_of_input(json.text()))
+            .unwrap();
+        assert!(rendered.starts_with("Source: config.json\n"));
+        assert!(rendered.ends_with("<EOF>"));
+    }
+}
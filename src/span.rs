@@ -0,0 +1,519 @@
+//! Span types and the coordinate system markers they are parameterised by.
+
+use core::convert::Infallible;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use crate::cli::ArgsFormat;
+use crate::coord::Coordinate;
+use crate::pos::{Pos, PosOverflow, Position};
+use crate::validate::{wide, SpanError};
+
+/// Zero-sized marker for cooked spans (byte coordinate system for error reporting)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cooked {}
+
+/// Zero-sized marker for raw spans, in the coordinate system of the format `F`.
+///
+/// Tagging raw spans with the format that produced them means a `Span<Raw<CliFormat>>`
+/// can only be cooked against [`CliFormat`](crate::CliFormat) and its input, and raw spans
+/// from two different formats can't be mixed.
+pub struct Raw<F>(Infallible, PhantomData<fn() -> F>);
+
+// `Raw<F>` is uninhabited, so these hold for every `F` (where `derive` would require them
+// of `F` too).
+
+impl<F> fmt::Debug for Raw<F> {
+    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {}
+    }
+}
+
+impl<F> Clone for Raw<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for Raw<F> {}
+
+impl<F> PartialEq for Raw<F> {
+    fn eq(&self, _other: &Self) -> bool {
+        match self.0 {}
+    }
+}
+
+impl<F> Eq for Raw<F> {}
+
+impl<F> Hash for Raw<F> {
+    fn hash<H: Hasher>(&self, _state: &mut H) {
+        match self.0 {}
+    }
+}
+
+/// A span in the input, with a start position and length.
+///
+/// The type parameter `C` defines the coordinate system ([`Raw`] or [`Cooked`], see
+/// [`Coordinate`]), and `P`
+/// the integer width positions are stored in (see [`Position`]; `u32` by default).
+pub struct Span<C = Cooked, P = Pos> {
+    /// Starting position of the span (in bytes, if cooked)
+    pub start: P,
+    /// Length of the span (in bytes, if cooked)
+    pub len: P,
+    _p: PhantomData<C>,
+}
+
+impl<C, P: Position> Span<C, P> {
+    /// Creates a new span with the given start position and length.
+    ///
+    /// Nothing is checked; see [`Span::try_new`] and [`Validate`](crate::Validate).
+    pub fn new(start: P, len: P) -> Self {
+        Self {
+            start,
+            len,
+            _p: PhantomData,
+        }
+    }
+
+    /// Creates a zero-width span at `pos`, for diagnostics that point at a position
+    /// rather than a range (e.g. "expected a value here")
+    pub fn point(pos: P) -> Self {
+        Self::new(pos, P::ZERO)
+    }
+
+    /// Start position of the span
+    pub fn start(&self) -> P {
+        self.start
+    }
+
+    /// Length of the span
+    pub fn len(&self) -> P {
+        self.len
+    }
+
+    /// Returns true if this span has zero length
+    pub fn is_empty(&self) -> bool {
+        self.len == P::ZERO
+    }
+
+    /// Creates a new span, failing if `start + len` overflows `P`
+    pub fn try_new(start: P, len: P) -> Result<Self, SpanError> {
+        start.checked_add(len).ok_or(SpanError::Overflow)?;
+        Ok(Self::new(start, len))
+    }
+
+    /// Creates a span from a start and (exclusive) end position, failing if `end < start`
+    pub fn from_bounds(start: P, end: P) -> Result<Self, SpanError> {
+        let len = end.checked_sub(start).ok_or(SpanError::Inverted {
+            start: wide(start),
+            end: wide(end),
+        })?;
+        Ok(Self::new(start, len))
+    }
+
+    /// Creates a span covering `range`, failing if it is inverted
+    pub fn from_range(range: Range<P>) -> Result<Self, SpanError> {
+        Self::from_bounds(range.start, range.end)
+    }
+
+    /// End position (start + length), saturating at `P::MAX` for a span built with
+    /// [`Span::new`] whose end overflows
+    pub fn end(&self) -> P {
+        self.start.saturating_add(self.len)
+    }
+
+    /// End position (start + length), or `None` if it overflows `P`
+    pub fn checked_end(&self) -> Option<P> {
+        self.start.checked_add(self.len)
+    }
+
+    /// Convert to another position width, failing if the start or end doesn't fit
+    pub fn try_cast<Q: Position>(self) -> Result<Span<C, Q>, PosOverflow> {
+        let start = self.start.cast::<Q>().ok_or(PosOverflow)?;
+        let len = self.len.cast::<Q>().ok_or(PosOverflow)?;
+        start.checked_add(len).ok_or(PosOverflow)?;
+        Ok(Span::new(start, len))
+    }
+
+    /// Relabel this span as being in the coordinate system `D`, without converting it.
+    ///
+    /// This is unchecked: nothing about the positions changes, so it is only correct when
+    /// the two coordinate systems agree on what a position means. To get a `Span<Cooked>`
+    /// from a raw span, cook it with [`ToCooked`](crate::ToCooked), which sees the input.
+    /// Requires the `assume-coordinate` feature, so every use is an explicit opt-in.
+    #[cfg(feature = "assume-coordinate")]
+    pub fn assume_coordinate<D>(self) -> Span<D, P> {
+        Span::new(self.start, self.len)
+    }
+
+    /// Creates a span from a start and (exclusive) end position, which must not precede it
+    fn from_start_end(start: P, end: P) -> Self {
+        debug_assert!(start <= end, "span end {end} precedes start {start}");
+        Self::new(start, end - start)
+    }
+
+    /// The smallest span covering both `self` and `other` (including any gap between them)
+    pub fn join(self, other: Self) -> Self {
+        Self::from_start_end(self.start.min(other.start), self.end().max(other.end()))
+    }
+
+    /// Alias for [`Span::join`]
+    pub fn union(self, other: Self) -> Self {
+        self.join(other)
+    }
+
+    /// The span covered by both `self` and `other`.
+    ///
+    /// Spans that only touch give an empty span at the shared boundary; spans with a gap
+    /// between them give `None`.
+    pub fn intersect(self, other: Self) -> Option<Self> {
+        let start = self.start.max(other.start);
+        let end = self.end().min(other.end());
+        (start <= end).then(|| Self::from_start_end(start, end))
+    }
+
+    /// Returns true if `pos` lies within the span (`start <= pos < end`)
+    pub fn contains(&self, pos: P) -> bool {
+        self.start <= pos && pos < self.end()
+    }
+
+    /// Returns true if `other` lies entirely within this span
+    pub fn contains_span(&self, other: &Self) -> bool {
+        self.start <= other.start && other.end() <= self.end()
+    }
+
+    /// Returns true if the two spans share at least one position
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.start < other.end() && other.start < self.end()
+    }
+
+    /// Move the start forward by `start_by` and the end back by `end_by`.
+    ///
+    /// Shrinking past the middle collapses to an empty span where the two ends meet.
+    pub fn shrink(self, start_by: P, end_by: P) -> Self {
+        let start = self.start.saturating_add(start_by).min(self.end());
+        let end = self.end().saturating_sub(end_by).max(start);
+        Self::from_start_end(start, end)
+    }
+
+    /// Move the start back by `start_by` and the end forward by `end_by`.
+    ///
+    /// The start saturates at zero and the end at `P::MAX`.
+    pub fn grow(self, start_by: P, end_by: P) -> Self {
+        let start = self.start.saturating_sub(start_by);
+        Self::from_start_end(start, self.end().saturating_add(end_by))
+    }
+
+    /// Split into two adjacent spans at `mid`, an offset relative to the start.
+    ///
+    /// Returns `None` if `mid` is past the end of the span.
+    pub fn split_at(self, mid: P) -> Option<(Self, Self)> {
+        (mid <= self.len).then(|| {
+            (
+                Self::new(self.start, mid),
+                Self::new(self.start + mid, self.len - mid),
+            )
+        })
+    }
+
+    /// Move the span by `delta` positions, keeping its length.
+    ///
+    /// Returns `None` if the start would move below zero or the end past `P::MAX`.
+    pub fn shift_by(self, delta: i64) -> Option<Self> {
+        let start = self.start.checked_add_signed(delta)?;
+        start.checked_add(self.len)?;
+        Some(Self::new(start, self.len))
+    }
+
+    /// The gap between two spans, from the end of the earlier to the start of the later.
+    ///
+    /// Adjacent spans give an empty gap; overlapping spans give `None`.
+    pub fn between(self, other: Self) -> Option<Self> {
+        let (first, second) = if self.start <= other.start {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        (first.end() <= second.start).then(|| Self::from_start_end(first.end(), second.start))
+    }
+
+    /// The smallest span covering every span in `spans`, or `None` if there are none.
+    ///
+    /// This is how a parent node's span is built from its children's.
+    pub fn cover<I: IntoIterator<Item = Self>>(spans: I) -> Option<Self> {
+        spans.into_iter().reduce(Self::join)
+    }
+}
+
+impl<F: ArgsFormat, P: Position> Span<Raw<F>, P> {
+    /// A point span one past the last arg in `input`
+    ///
+    /// # Panics
+    ///
+    /// If `input` has more args than `P` can address.
+    pub fn end_of_args(input: &F::Input<'_>) -> Self {
+        let len = F::args(input).len();
+        Self::point(P::from_usize(len).expect("input too long for span position width"))
+    }
+}
+
+impl<P: Position> Span<Cooked, P> {
+    /// A point span one past the last byte of `input`
+    ///
+    /// # Panics
+    ///
+    /// If `input` is longer than `P` can address.
+    pub fn end_of_input<T: AsRef<[u8]> + ?Sized>(input: &T) -> Self {
+        Self::point(
+            P::from_usize(input.as_ref().len()).expect("input too long for span position width"),
+        )
+    }
+}
+
+impl<C> From<Span<C, u32>> for Span<C, u64> {
+    fn from(span: Span<C, u32>) -> Self {
+        Span::new(span.start.into(), span.len.into())
+    }
+}
+
+// Spans are shown as the byte range `start..end` when cooked, and as `start+len` (e.g. an
+// arg index and a count of args) otherwise. `Debug` adds the coordinate system's name.
+
+impl<C: Coordinate, P: Position> fmt::Display for Span<C, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if C::IS_BYTE_OFFSET {
+            write!(f, "{}..{}", self.start, self.end())
+        } else {
+            write!(f, "{}+{}", self.start, self.len)
+        }
+    }
+}
+
+impl<C: Coordinate, P: Position> fmt::Debug for Span<C, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{self}]", C::NAME)
+    }
+}
+
+// The marker types are uninhabited, so these impls are written by hand to avoid
+// the `C: Clone`/`C: PartialEq`/... bounds that `derive` would add.
+
+impl<C, P: Clone> Clone for Span<C, P> {
+    fn clone(&self) -> Self {
+        Self {
+            start: self.start.clone(),
+            len: self.len.clone(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<C, P: Copy> Copy for Span<C, P> {}
+
+impl<C, P: PartialEq> PartialEq for Span<C, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.start == other.start && self.len == other.len
+    }
+}
+
+impl<C, P: Eq> Eq for Span<C, P> {}
+
+impl<C, P: Hash> Hash for Span<C, P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.start.hash(state);
+        self.len.hash(state);
+    }
+}
+
+/// Trait for types that can be annotated with a Span.
+pub trait Spannable<C = Cooked, P = Pos>: Sized {
+    /// Annotate this value with a span, wrapping it in `Spanned<Self, C, P>`
+    fn with_span(self, span: Span<C, P>) -> Spanned<Self, C, P>;
+}
+
+impl<T, C, P> Spannable<C, P> for T {
+    fn with_span(self, span: Span<C, P>) -> Spanned<Self, C, P> {
+        Spanned { node: self, span }
+    }
+}
+
+/// A value of type `T` annotated with its `Span<C, P>`
+pub struct Spanned<T, C = Cooked, P = Pos> {
+    /// The actual data/value being wrapped
+    pub node: T,
+    /// The span information indicating the position and length in the source
+    pub span: Span<C, P>,
+}
+
+impl<T, C, P> Spanned<T, C, P> {
+    /// Map the node value with a function
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U, C, P> {
+        Spanned {
+            node: f(self.node),
+            span: self.span,
+        }
+    }
+
+    /// Map the span with a function
+    pub fn map_span<D, Q>(self, f: impl FnOnce(Span<C, P>) -> Span<D, Q>) -> Spanned<T, D, Q> {
+        Spanned {
+            node: self.node,
+            span: f(self.span),
+        }
+    }
+}
+
+// Written by hand for the same reason as `Span`'s: only `T` and `P` need the trait.
+
+impl<T: Clone, C, P: Clone> Clone for Spanned<T, C, P> {
+    fn clone(&self) -> Self {
+        Spanned {
+            node: self.node.clone(),
+            span: self.span.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq, C, P: PartialEq> PartialEq for Spanned<T, C, P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node && self.span == other.span
+    }
+}
+
+impl<T: Eq, C, P: Eq> Eq for Spanned<T, C, P> {}
+
+impl<T: Hash, C, P: Hash> Hash for Spanned<T, C, P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.node.hash(state);
+        self.span.hash(state);
+    }
+}
+
+impl<T: fmt::Debug, C, P> fmt::Debug for Spanned<T, C, P>
+where
+    Span<C, P>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Spanned")
+            .field("node", &self.node)
+            .field("span", &self.span)
+            .finish()
+    }
+}
+
+impl<T: fmt::Display, C, P> fmt::Display for Spanned<T, C, P>
+where
+    Span<C, P>: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.node, self.span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord::RawCoordinate;
+
+    /// A coordinate system added outside the crate, which derives nothing
+    enum Lines {}
+
+    impl RawCoordinate for Lines {
+        const NAME: &'static str = "Lines";
+    }
+
+    #[test]
+    fn spanned_needs_nothing_of_the_coordinate() {
+        let spanned = "x".with_span(Span::<Lines>::new(2, 1));
+        assert_eq!(spanned.clone(), spanned);
+        assert_ne!(spanned, "x".with_span(Span::new(3, 1)));
+        assert_eq!(
+            format!("{spanned:?}"),
+            r#"Spanned { node: "x", span: Lines[2+1] }"#
+        );
+    }
+
+    fn span(start: Pos, end: Pos) -> Span {
+        Span::from_bounds(start, end).unwrap()
+    }
+
+    #[test]
+    fn join_covers_both_and_the_gap() {
+        assert_eq!(span(2, 4).join(span(6, 9)), span(2, 9));
+        assert_eq!(span(6, 9).join(span(2, 4)), span(2, 9));
+        assert_eq!(span(2, 9).join(span(3, 4)), span(2, 9));
+        assert_eq!(span(2, 4).join(Span::point(4)), span(2, 4));
+        assert_eq!(span(2, 4).union(span(4, 5)), span(2, 5));
+    }
+
+    #[test]
+    fn intersect_keeps_the_shared_part() {
+        assert_eq!(span(2, 6).intersect(span(4, 9)), Some(span(4, 6)));
+        assert_eq!(span(2, 9).intersect(span(4, 6)), Some(span(4, 6)));
+        // Touching spans share a boundary, but no bytes
+        assert_eq!(span(2, 4).intersect(span(4, 6)), Some(Span::point(4)));
+        assert_eq!(span(2, 4).intersect(span(5, 6)), None);
+    }
+
+    #[test]
+    fn between_is_the_gap() {
+        assert_eq!(span(2, 4).between(span(7, 9)), Some(span(4, 7)));
+        assert_eq!(span(7, 9).between(span(2, 4)), Some(span(4, 7)));
+        assert_eq!(span(2, 4).between(span(4, 9)), Some(Span::point(4)));
+        assert_eq!(span(2, 5).between(span(4, 9)), None);
+    }
+
+    #[test]
+    fn contains_and_overlaps_exclude_the_end() {
+        assert!(span(2, 4).contains(2));
+        assert!(span(2, 4).contains(3));
+        assert!(!span(2, 4).contains(4));
+        assert!(!Span::<Cooked>::point(2).contains(2));
+        assert!(span(2, 6).contains_span(&span(2, 6)));
+        assert!(span(2, 6).contains_span(&Span::point(6)));
+        assert!(!span(2, 6).contains_span(&span(5, 7)));
+        assert!(span(2, 4).overlaps(&span(3, 6)));
+        assert!(!span(2, 4).overlaps(&span(4, 6)));
+    }
+
+    #[test]
+    fn shrink_and_grow_saturate() {
+        assert_eq!(span(2, 8).shrink(1, 2), span(3, 6));
+        assert_eq!(span(2, 8).shrink(6, 0), Span::point(8));
+        // Past the middle, the span collapses where the moved start is
+        assert_eq!(span(2, 8).shrink(4, 4), Span::point(6));
+        assert_eq!(span(2, 8).shrink(Pos::MAX, Pos::MAX), Span::point(8));
+        assert_eq!(span(2, 8).grow(1, 2), span(1, 10));
+        assert_eq!(span(2, 8).grow(5, 0), span(0, 8));
+        assert_eq!(span(2, 8).grow(0, Pos::MAX), span(2, Pos::MAX));
+    }
+
+    #[test]
+    fn split_at_is_relative_to_the_start() {
+        assert_eq!(span(2, 8).split_at(0), Some((Span::point(2), span(2, 8))));
+        assert_eq!(span(2, 8).split_at(2), Some((span(2, 4), span(4, 8))));
+        assert_eq!(span(2, 8).split_at(6), Some((span(2, 8), Span::point(8))));
+        assert_eq!(span(2, 8).split_at(7), None);
+    }
+
+    #[test]
+    fn shift_by_keeps_the_length() {
+        assert_eq!(span(2, 8).shift_by(3), Some(span(5, 11)));
+        assert_eq!(span(2, 8).shift_by(-2), Some(span(0, 6)));
+        assert_eq!(span(2, 8).shift_by(-3), None);
+        assert_eq!(span(2, 8).shift_by(i64::from(Pos::MAX)), None);
+    }
+
+    #[test]
+    fn cover_joins_every_span() {
+        assert_eq!(
+            Span::cover([span(5, 6), span(1, 2), span(3, 9)]),
+            Some(span(1, 9))
+        );
+        assert_eq!(Span::cover([span(4, 4)]), Some(Span::point(4)));
+        assert_eq!(Span::<Cooked>::cover([]), None);
+    }
+}
@@ -0,0 +1,229 @@
+//! Converting cooked byte offsets back into a format's raw coordinates.
+//!
+//! This mirrors [`ToCooked`](crate::ToCooked), so for a format `F` the round-trip laws are:
+//!
+//! - a raw span that cooks to `c` converts back from `c` to itself
+//! - a cooked span that converts to a raw span `r` is what `r` cooks to
+//!
+//! A cooked span that falls between raw positions (e.g. half of a CLI arg) is an error.
+//!
+//! The first law has one exception, for CLI formats: an empty arg takes up no bytes, so a
+//! span of just that arg cooks to the same point as a gap beside it, and an empty first arg
+//! to the same point as the start of the line. Such a point converts back to a gap: the one
+//! after the arg ending there, or the start of the line. In `["a", "", "b"]`, `Raw[1+1]` and
+//! `Raw[2+0]` both cook to `Cooked[2..2]`, which converts back to `Raw[2+0]`.
+
+use core::fmt;
+
+use crate::format::{CookSpan, Format};
+use crate::pos::{Pos, Position};
+use crate::span::{Cooked, Span};
+use crate::validate::SpanError;
+
+/// Why a cooked span has no equivalent in the format's raw coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawError {
+    /// The span doesn't fit the (rendered) input, or a boundary falls inside a UTF-8 encoded
+    /// character
+    Span(SpanError),
+    /// The span includes (or is a point on) the separator between two CLI args
+    OnSeparator {
+        /// Byte position of the separator
+        pos: u64,
+    },
+    /// A span boundary falls inside a CLI arg, so the span straddles it
+    Straddles {
+        /// Index of the arg
+        arg: u64,
+        /// Byte position of the boundary
+        pos: u64,
+    },
+    /// The raw position doesn't fit in the span's position width
+    Overflow,
+    /// The format has no conversion out of cooked spans (see [`CookRaw::uncook_raw`](crate::CookRaw::uncook_raw))
+    Unsupported,
+}
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RawError::Span(e) => e.fmt(f),
+            RawError::OnSeparator { pos } => {
+                write!(f, "span includes the separator between args at {pos}")
+            }
+            RawError::Straddles { arg, pos } => {
+                write!(f, "span boundary {pos} falls inside arg {arg}")
+            }
+            RawError::Overflow => f.write_str("raw position overflows the span's width"),
+            RawError::Unsupported => f.write_str("format can't convert cooked spans to raw"),
+        }
+    }
+}
+
+impl std::error::Error for RawError {}
+
+impl From<SpanError> for RawError {
+    fn from(e: SpanError) -> Self {
+        RawError::Span(e)
+    }
+}
+
+/// Spans that can be recovered from a cooked span for the format `F`.
+///
+/// This is implemented on the raw side (the conversion's target) so that the identity impl
+/// for [`Cooked`] and the impls for each format's `Raw<F>` don't overlap; call it through
+/// [`ToRaw`].
+pub trait FromCooked<'input, F: Format, P: Position = Pos>: Sized {
+    /// Convert `span` out of the cooked (byte offset) coordinate system
+    fn try_from_cooked(
+        span: Span<Cooked, P>,
+        format: &F,
+        input: &F::Input<'input>,
+    ) -> Result<Self, RawError>;
+}
+
+/// The reverse of [`ToCooked`](crate::ToCooked): convert a cooked span into the format's
+/// own coordinate system.
+pub trait ToRaw<'input, F: Format, P: Position = Pos>: Sized {
+    /// Convert this span into `F`'s coordinate system, or report why it has no equivalent
+    fn try_to_raw(
+        self,
+        format: &F,
+        input: &F::Input<'input>,
+    ) -> Result<Span<F::SpanType, P>, RawError>;
+
+    /// Convert this span into `F`'s coordinate system
+    ///
+    /// # Panics
+    ///
+    /// If [`ToRaw::try_to_raw`] fails.
+    #[inline]
+    fn to_raw(self, format: &F, input: &F::Input<'input>) -> Span<F::SpanType, P> {
+        self.try_to_raw(format, input)
+            .unwrap_or_else(|e| panic!("failed to convert span to raw: {e}"))
+    }
+}
+
+impl<'input, F: Format, P: Position> ToRaw<'input, F, P> for Span<Cooked, P> {
+    #[inline]
+    fn try_to_raw(
+        self,
+        format: &F,
+        input: &F::Input<'input>,
+    ) -> Result<Span<F::SpanType, P>, RawError> {
+        F::SpanType::uncook_span(format, self, input)
+    }
+}
+
+impl<'input, F: Format, C: CookSpan<F>, P: Position> FromCooked<'input, F, P> for Span<C, P> {
+    #[inline]
+    fn try_from_cooked(
+        span: Span<Cooked, P>,
+        format: &F,
+        input: &F::Input<'input>,
+    ) -> Result<Self, RawError> {
+        C::uncook_span(format, span, input)
+    }
+}
+
+/// Convert a raw position into the span's position width
+pub(crate) fn raw_pos<P: Position>(n: usize) -> Result<P, RawError> {
+    P::from_usize(n).ok_or(RawError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{CliFormat, RawFormat, ToCooked};
+    use crate::span::Raw;
+
+    const ARGS: &[&[&str]] = &[
+        &[],
+        &["--foo", "bar"],
+        &["-v", "caf\u{e9}", "--level=9000"],
+        &["a", "", "b"],
+        &["", ""],
+    ];
+
+    #[test]
+    fn cli_raw_round_trips() {
+        for &args in ARGS {
+            for start in 0..=args.len() as u32 {
+                for len in 0..=args.len() as u32 - start {
+                    let raw = Span::<Raw<CliFormat>>::new(start, len);
+                    let cooked = raw.to_cooked(&CliFormat, args);
+                    let back: Span<Raw<CliFormat>> = cooked.to_raw(&CliFormat, args);
+                    if cooked.len == 0 {
+                        // Empty args can share a point with a gap, which converts back to
+                        // whichever span `to_raw` picks, and that must cook to the point
+                        assert!(back.len == 0 || raw == back, "{args:?}: {raw:?}");
+                        assert_eq!(back.to_cooked(&CliFormat, args), cooked, "{args:?}");
+                    } else {
+                        assert_eq!(back, raw, "{args:?}: {raw:?} cooked to {cooked:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cli_empty_arg_shares_a_point() {
+        let args = ["a", "", "b"];
+        let cook =
+            |start, len| Span::<Raw<CliFormat>>::new(start, len).to_cooked(&CliFormat, &args[..]);
+        assert_eq!(cook(1, 1), Span::<Cooked>::point(2));
+        assert_eq!(cook(2, 0), Span::<Cooked>::point(2));
+        let back: Span<Raw<CliFormat>> = Span::<Cooked>::point(2).to_raw(&CliFormat, &args[..]);
+        assert_eq!(back, Span::point(2));
+    }
+
+    #[test]
+    fn cli_cooked_round_trips() {
+        for &args in ARGS {
+            let line_len = crate::cli::render_args(args).len() as u32;
+            for start in 0..=line_len {
+                for len in 0..=line_len - start {
+                    let cooked = Span::<Cooked>::new(start, len);
+                    if let Ok(raw) = cooked.try_to_raw(&CliFormat, args) {
+                        assert_eq!(raw.to_cooked(&CliFormat, args), cooked, "{args:?}: {raw:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cli_cooked_errors() {
+        let args = ["--foo", "bar"];
+        let raw = |start, len| Span::<Cooked>::new(start, len).try_to_raw(&CliFormat, &args[..]);
+        assert_eq!(raw(0, 6), Err(RawError::OnSeparator { pos: 5 }));
+        assert_eq!(raw(2, 3), Err(RawError::Straddles { arg: 0, pos: 2 }));
+        assert_eq!(raw(5, 0), Ok(Span::point(1)));
+        assert_eq!(raw(7, 0), Err(RawError::Straddles { arg: 1, pos: 7 }));
+        assert_eq!(
+            raw(0, 10),
+            Err(RawError::Span(SpanError::EndOutOfRange {
+                end: 10,
+                input_len: 9
+            }))
+        );
+    }
+
+    #[test]
+    fn raw_format_round_trips() {
+        let input = "h\u{e9}llo, w\u{f6}rld \u{1f600}";
+        let chars = input.chars().count() as u32;
+        for start in 0..=chars {
+            for len in 0..=chars - start {
+                let raw = Span::<Raw<RawFormat>>::new(start, len);
+                let cooked = raw.to_cooked(&RawFormat, input);
+                assert_eq!(cooked.to_raw(&RawFormat, input), raw);
+            }
+        }
+        let mid_char = Span::<Cooked>::new(2, 1).try_to_raw(&RawFormat, input);
+        assert_eq!(
+            mid_char,
+            Err(RawError::Span(SpanError::NotCharBoundary { pos: 2 }))
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Checking spans against the input they claim to describe.
+
+use core::fmt;
+
+use crate::cli::ArgsFormat;
+use crate::format::RawFormat;
+use crate::pos::Position;
+use crate::span::{Cooked, Raw, Span};
+
+/// A span that is malformed, or doesn't fit the input it is used with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanError {
+    /// `start + len` overflows the span's position width
+    Overflow,
+    /// The end bound given precedes the start
+    Inverted {
+        /// Start position
+        start: u64,
+        /// End position
+        end: u64,
+    },
+    /// The span starts past the end of the input
+    StartOutOfRange {
+        /// Start position of the span
+        start: u64,
+        /// Length of the input
+        input_len: u64,
+    },
+    /// The span ends past the end of the input
+    EndOutOfRange {
+        /// End position of the span
+        end: u64,
+        /// Length of the input
+        input_len: u64,
+    },
+    /// The span starts or ends inside a UTF-8 encoded character
+    NotCharBoundary {
+        /// The offending byte position
+        pos: u64,
+    },
+}
+
+impl fmt::Display for SpanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpanError::Overflow => f.write_str("span end overflows its position width"),
+            SpanError::Inverted { start, end } => {
+                write!(f, "span end {end} precedes its start {start}")
+            }
+            SpanError::StartOutOfRange { start, input_len } => {
+                write!(
+                    f,
+                    "span starts at {start}, past the end of the input ({input_len})"
+                )
+            }
+            SpanError::EndOutOfRange { end, input_len } => {
+                write!(
+                    f,
+                    "span ends at {end}, past the end of the input ({input_len})"
+                )
+            }
+            SpanError::NotCharBoundary { pos } => {
+                write!(f, "span boundary {pos} is not on a char boundary")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpanError {}
+
+pub(crate) fn wide<P: Position>(pos: P) -> u64 {
+    pos.cast().unwrap_or(u64::MAX)
+}
+
+/// Spans that can be checked against an input of type `I`.
+///
+/// Implemented for cooked spans over `[u8]` and `str` (byte offsets, and for `str`, char
+/// boundaries), for raw spans of a CLI format over that format's input (arg indices), and for
+/// raw [`RawFormat`] spans over `str` (chars).
+pub trait Validate<I: ?Sized> {
+    /// Check that the span lies within `input`
+    fn validate(&self, input: &I) -> Result<(), SpanError>;
+}
+
+/// Check `start <= end <= input_len`, returning the span's bounds as `usize`
+pub(crate) fn check_bounds<C, P: Position>(
+    span: &Span<C, P>,
+    input_len: usize,
+) -> Result<(usize, usize), SpanError> {
+    let end = span.checked_end().ok_or(SpanError::Overflow)?;
+    let out_of_range = |pos: P| pos.to_usize().filter(|&p| p <= input_len);
+    let start = out_of_range(span.start).ok_or(SpanError::StartOutOfRange {
+        start: wide(span.start),
+        input_len: input_len as u64,
+    })?;
+    let end = out_of_range(end).ok_or(SpanError::EndOutOfRange {
+        end: wide(end),
+        input_len: input_len as u64,
+    })?;
+    Ok((start, end))
+}
+
+/// [`check_bounds`] for a byte offset span into `input`, which must also start and end on
+/// char boundaries
+pub(crate) fn check_str<C, P: Position>(
+    span: &Span<C, P>,
+    input: &str,
+) -> Result<(usize, usize), SpanError> {
+    let (start, end) = check_bounds(span, input.len())?;
+    match [start, end]
+        .into_iter()
+        .find(|&pos| !input.is_char_boundary(pos))
+    {
+        Some(pos) => Err(SpanError::NotCharBoundary { pos: pos as u64 }),
+        None => Ok((start, end)),
+    }
+}
+
+impl<P: Position> Validate<[u8]> for Span<Cooked, P> {
+    fn validate(&self, input: &[u8]) -> Result<(), SpanError> {
+        check_bounds(self, input.len()).map(|_| ())
+    }
+}
+
+impl<P: Position> Validate<str> for Span<Cooked, P> {
+    fn validate(&self, input: &str) -> Result<(), SpanError> {
+        check_str(self, input).map(|_| ())
+    }
+}
+
+impl<'input, F: ArgsFormat, P: Position> Validate<F::Input<'input>> for Span<Raw<F>, P> {
+    /// Raw CLI spans count args, so are checked against the number of args
+    fn validate(&self, input: &F::Input<'input>) -> Result<(), SpanError> {
+        check_bounds(self, F::args(input).len()).map(|_| ())
+    }
+}
+
+impl<P: Position> Validate<str> for Span<Raw<RawFormat>, P> {
+    /// Raw [`RawFormat`] spans count chars, so are checked against the number of chars
+    fn validate(&self, input: &str) -> Result<(), SpanError> {
+        check_bounds(self, input.chars().count()).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::CliFormat;
+
+    #[test]
+    fn checks_construction() {
+        assert_eq!(
+            Span::<Cooked>::try_new(u32::MAX - 1, 1),
+            Ok(Span::new(u32::MAX - 1, 1))
+        );
+        assert_eq!(
+            Span::<Cooked>::try_new(u32::MAX, 1),
+            Err(SpanError::Overflow)
+        );
+        assert_eq!(Span::<Cooked>::from_bounds(2, 5), Ok(Span::new(2, 3)));
+        assert_eq!(Span::<Cooked>::from_bounds(2, 2), Ok(Span::point(2)));
+        assert_eq!(
+            Span::<Cooked>::from_bounds(5, 2),
+            Err(SpanError::Inverted { start: 5, end: 2 })
+        );
+        assert_eq!(Span::<Cooked>::from_range(1..4), Ok(Span::new(1, 3)));
+    }
+
+    #[test]
+    fn end_never_overflows() {
+        let span = Span::<Cooked>::new(u32::MAX, 1);
+        assert_eq!(span.end(), u32::MAX);
+        assert_eq!(span.checked_end(), None);
+        assert_eq!(span.validate(&b"abc"[..]), Err(SpanError::Overflow));
+    }
+
+    #[test]
+    fn validates_cooked_spans() {
+        let text = "caf\u{e9}";
+        assert_eq!(Span::<Cooked>::new(0, 5).validate(text), Ok(()));
+        assert_eq!(Span::<Cooked>::point(5).validate(text), Ok(()));
+        assert_eq!(
+            Span::<Cooked>::new(0, 4).validate(text),
+            Err(SpanError::NotCharBoundary { pos: 4 })
+        );
+        // Bytes have no char boundaries to check
+        assert_eq!(Span::<Cooked>::new(0, 4).validate(text.as_bytes()), Ok(()));
+        assert_eq!(
+            Span::<Cooked>::new(4, 2).validate(text),
+            Err(SpanError::EndOutOfRange {
+                end: 6,
+                input_len: 5
+            })
+        );
+    }
+
+    #[test]
+    fn validates_raw_spans_in_their_own_units() {
+        let args = ["--foo", "bar"];
+        assert_eq!(
+            Span::<Raw<CliFormat>>::new(0, 2).validate(&args[..]),
+            Ok(())
+        );
+        assert_eq!(
+            Span::<Raw<CliFormat>>::new(3, 0).validate(&args[..]),
+            Err(SpanError::StartOutOfRange {
+                start: 3,
+                input_len: 2
+            })
+        );
+        // Four chars, though five bytes
+        let text = "caf\u{e9}";
+        assert_eq!(Span::<Raw<RawFormat>>::new(0, 4).validate(text), Ok(()));
+        assert!(Span::<Raw<RawFormat>>::new(0, 5).validate(text).is_err());
+    }
+
+    #[test]
+    fn errors_show_in_the_input_units() {
+        let error = Span::<Cooked>::new(2, 3).validate("ab").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "span ends at 5, past the end of the input (2)"
+        );
+    }
+}